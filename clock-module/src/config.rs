@@ -1,26 +1,68 @@
+use chrono_tz::Tz;
 use dynisland_core::{
-    abi::module::ActivityIdentifier,
+    abi::{log, module::ActivityIdentifier},
     d_macro::{MultiWidgetConfig, OptDeserializeConfig},
 };
 use serde::Serialize;
 
+use crate::timezone::parse_timezone;
+
 #[derive(Debug, Serialize, Clone, MultiWidgetConfig, OptDeserializeConfig)]
 pub struct ClockConfig {
     pub(crate) format_24h: bool,
+    /// chrono strftime format of the compact mode, empty to use `format_24h`
+    pub(crate) format: String,
     pub(crate) hour_hand_color: String,
     pub(crate) minute_hand_color: String,
     pub(crate) tick_color: String,
     pub(crate) circle_color: String,
+    /// names of the tz database shown in expanded mode
+    pub(crate) timezones: Vec<String>,
+    /// timezone of the minimal and compact mode, empty for the local one
+    pub(crate) compact_timezone: String,
 }
 
 impl Default for ClockConfig {
     fn default() -> Self {
         Self {
             format_24h: true,
+            format: String::new(),
             hour_hand_color: String::from("white"),
             minute_hand_color: String::from("white"),
             circle_color: String::from("lightgray"),
             tick_color: String::from("lightgray"),
+            timezones: Vec::new(),
+            compact_timezone: String::new(),
+        }
+    }
+}
+
+impl ClockConfig {
+    /// Returns the valid timezones with their names, the invalid ones are logged
+    pub fn parsed_timezones(&self) -> Vec<(String, Tz)> {
+        self.timezones
+            .iter()
+            .filter_map(|name| match parse_timezone(name) {
+                Ok(timezone) => Some((name.clone(), timezone)),
+                Err(err) => {
+                    log::warn!("{err}");
+                    None
+                }
+            })
+            .collect()
+    }
+
+    /// Returns the timezone of the compact mode, `None` for the local one
+    pub fn parsed_compact_timezone(&self) -> Option<Tz> {
+        if self.compact_timezone.is_empty() {
+            return None;
+        }
+        match parse_timezone(&self.compact_timezone) {
+            Ok(timezone) => Some(timezone),
+            Err(err) => {
+                log::warn!("{err}, using the local timezone");
+                None
+            }
         }
     }
 }
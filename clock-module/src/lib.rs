@@ -7,6 +7,7 @@ use dynisland_core::abi::{
 };
 pub mod config;
 pub mod module;
+pub mod timezone;
 pub mod widget;
 
 use module::new;
@@ -29,7 +29,7 @@ use ron::ser::PrettyConfig;
 
 use crate::{
     config::{get_conf_idx, ClockConfigMain, DeClockConfigMain},
-    widget::{clock::Clock, compact::Compact, get_activity},
+    widget::{clock::Clock, compact::Compact, expanded::WorldClock, get_activity},
     NAME,
 };
 
@@ -172,7 +172,10 @@ fn producer(module: &ClockModule) {
                 .compact_mode_widget()
                 .and_downcast::<Compact>()
                 .unwrap();
+            let compact_timezone = config.parsed_compact_timezone();
             comp.set_format_24h(config.format_24h);
+            comp.set_format(config.format.clone());
+            comp.set_timezone(compact_timezone);
             let clock = act
                 .blocking_lock()
                 .get_activity_widget()
@@ -183,7 +186,16 @@ fn producer(module: &ClockModule) {
             clock.set_minute_hand_color(config.minute_hand_color.clone());
             clock.set_circle_color(config.circle_color.clone());
             clock.set_tick_color(config.tick_color.clone());
+            clock.set_timezone(compact_timezone);
             clock.queue_draw();
+            let world_clock = act
+                .blocking_lock()
+                .get_activity_widget()
+                .expanded_mode_widget()
+                .and_downcast::<WorldClock>()
+                .unwrap();
+            world_clock.set_format_24h(config.format_24h);
+            world_clock.set_timezones(config.parsed_timezones());
         }
         time_list.push(
             activities
@@ -0,0 +1,124 @@
+use std::fmt::Write;
+
+use chrono::{format::Item, DateTime, Local, NaiveDateTime, Offset, Timelike};
+use chrono_tz::Tz;
+
+/// Parses a name of the tz database, like `Europe/Rome`
+pub fn parse_timezone(name: &str) -> Result<Tz, String> {
+    name.parse::<Tz>()
+        .map_err(|err| format!("invalid timezone {name}: {err}"))
+}
+
+/// Returns the time shown by a clock in `timezone`, the local timezone if it's `None`
+pub fn time_in(time: &DateTime<Local>, timezone: Option<Tz>) -> NaiveDateTime {
+    match timezone {
+        Some(timezone) => time.with_timezone(&timezone).naive_local(),
+        None => time.naive_local(),
+    }
+}
+
+/// Formats `time` in `timezone` with `items`, in the local timezone if it's `None`
+pub fn format_in(time: &DateTime<Local>, timezone: Option<Tz>, items: &[Item]) -> String {
+    let mut text = String::new();
+    // an item that can't be formatted stops the text there
+    let _ = match timezone {
+        Some(timezone) => write!(
+            text,
+            "{}",
+            time.with_timezone(&timezone)
+                .format_with_items(items.iter())
+        ),
+        None => write!(text, "{}", time.format_with_items(items.iter())),
+    };
+    text
+}
+
+/// Returns the offset from UTC of `timezone` at `time`, in seconds
+pub fn offset_in(time: &DateTime<Local>, timezone: Tz) -> i32 {
+    time.with_timezone(&timezone)
+        .offset()
+        .fix()
+        .local_minus_utc()
+}
+
+/// Formats an offset in seconds like `UTC`, `UTC+2` or `UTC-3:30`
+pub fn format_offset(offset: i32) -> String {
+    let sign = if offset < 0 { '-' } else { '+' };
+    let (hours, minutes) = (offset.abs() / 3600, offset.abs() % 3600 / 60);
+    match (hours, minutes) {
+        (0, 0) => "UTC".to_string(),
+        (hours, 0) => format!("UTC{sign}{hours}"),
+        (hours, minutes) => format!("UTC{sign}{hours}:{minutes:02}"),
+    }
+}
+
+/// Returns true from 6:00 to 18:00
+pub fn is_day(time: &NaiveDateTime) -> bool {
+    (6..18).contains(&time.hour())
+}
+
+/// Returns the name of the city of the timezone, `America/New_York` is `New York`
+pub fn city_name(timezone: &str) -> String {
+    timezone
+        .rsplit('/')
+        .next()
+        .unwrap_or(timezone)
+        .replace('_', " ")
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    use super::*;
+
+    fn noon_utc() -> DateTime<Local> {
+        Utc.with_ymd_and_hms(2024, 1, 1, 12, 0, 0)
+            .unwrap()
+            .with_timezone(&Local)
+    }
+
+    fn at(hour: u32, minute: u32) -> NaiveDateTime {
+        NaiveDate::from_ymd_opt(2024, 1, 1)
+            .unwrap()
+            .and_hms_opt(hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn offsets() {
+        assert_eq!(format_offset(0), "UTC");
+        assert_eq!(format_offset(2 * 3600), "UTC+2");
+        assert_eq!(format_offset(-5 * 3600), "UTC-5");
+        assert_eq!(format_offset(5 * 3600 + 1800), "UTC+5:30");
+        assert_eq!(format_offset(-(3 * 3600 + 1800)), "UTC-3:30");
+        assert_eq!(format_offset(-1800), "UTC-0:30");
+        assert_eq!(format_offset(5 * 3600 + 45 * 60), "UTC+5:45");
+    }
+
+    #[test]
+    fn timezones() {
+        let kolkata = parse_timezone("Asia/Kolkata").unwrap();
+        assert_eq!(offset_in(&noon_utc(), kolkata), 5 * 3600 + 1800);
+        assert_eq!(time_in(&noon_utc(), Some(kolkata)), at(17, 30));
+        let new_york = parse_timezone("America/New_York").unwrap();
+        assert_eq!(offset_in(&noon_utc(), new_york), -5 * 3600);
+        assert!(parse_timezone("Europe/Nowhere").is_err());
+    }
+
+    #[test]
+    fn cities() {
+        assert_eq!(city_name("America/New_York"), "New York");
+        assert_eq!(city_name("America/Argentina/Buenos_Aires"), "Buenos Aires");
+        assert_eq!(city_name("UTC"), "UTC");
+    }
+
+    #[test]
+    fn day_and_night() {
+        assert!(!is_day(&at(5, 59)));
+        assert!(is_day(&at(6, 0)));
+        assert!(is_day(&at(17, 59)));
+        assert!(!is_day(&at(18, 0)));
+        assert!(!is_day(&at(0, 0)));
+    }
+}
@@ -1,6 +1,7 @@
 use std::{cell::RefCell, f64::consts::PI};
 
 use chrono::{Local, Timelike};
+use chrono_tz::Tz;
 use dynisland_core::{
     abi::{gdk, glib, gtk, log},
     cast_dyn_any,
@@ -21,6 +22,8 @@ use gtk::{
     BinLayout,
 };
 
+use crate::timezone::time_in;
+
 glib::wrapper! {
     pub struct Clock(ObjectSubclass<ClockPriv>)
     @extends gtk::Widget;
@@ -38,6 +41,8 @@ pub struct ClockPriv {
     circle_color: RefCell<RGBA>,
 
     time: RefCell<chrono::DateTime<Local>>,
+    /// `None` for the local timezone
+    timezone: RefCell<Option<Tz>>,
 }
 
 #[glib::object_subclass]
@@ -59,6 +64,7 @@ impl Default for ClockPriv {
             circle_color: RefCell::new(RGBA::parse("lightgray").unwrap()),
             tick_color: RefCell::new(RGBA::parse("lightgray").unwrap()),
             time: RefCell::new(Local::now()),
+            timezone: RefCell::new(None),
         }
     }
 }
@@ -154,8 +160,9 @@ impl WidgetImpl for ClockPriv {
         }
         ctx.restore().unwrap();
 
-        let hour = self.time.borrow().hour() as f64;
-        let minute = self.time.borrow().minute() as f64;
+        let time = time_in(&self.time.borrow(), *self.timezone.borrow());
+        let hour = time.hour() as f64;
+        let minute = time.minute() as f64;
 
         //hour hand
         ctx.set_line_cap(gdk::cairo::LineCap::Round);
@@ -206,6 +213,11 @@ impl Clock {
     pub fn get_time(&self) -> chrono::DateTime<Local> {
         *self.imp().time.borrow()
     }
+    /// `None` for the local timezone
+    pub fn set_timezone(&self, timezone: Option<Tz>) {
+        self.imp().timezone.replace(timezone);
+        self.queue_draw();
+    }
     /// must be used from the gtk main context
     pub fn set_time(&self, time: chrono::DateTime<Local>) {
         let old_time = self.get_time();
@@ -1,8 +1,12 @@
 use std::cell::RefCell;
 
-use chrono::{Local, Timelike};
+use chrono::{
+    format::{Item, StrftimeItems},
+    Local,
+};
+use chrono_tz::Tz;
 use dynisland_core::{
-    abi::{glib, gtk},
+    abi::{glib, gtk, log},
     cast_dyn_any,
     dynamic_activity::DynamicActivity,
     graphics::widgets::{rolling_char::RollingChar, scrolling_label::ScrollingLabel},
@@ -13,39 +17,55 @@ use glib::{
     types::StaticTypeExt,
     Object, Properties,
 };
-use gtk::{prelude::WidgetExt, subclass::widget::*, BinLayout, CompositeTemplate, TemplateChild};
+use gtk::{
+    prelude::{BoxExt, Cast, WidgetExt},
+    subclass::widget::*,
+    BinLayout, CompositeTemplate, TemplateChild,
+};
 use object::{ObjectImpl, ObjectImplExt};
 use types::{ObjectSubclass, ObjectSubclassExt, ObjectSubclassIsExt};
 
+use crate::timezone::format_in;
+
 glib::wrapper! {
     pub struct Compact(ObjectSubclass<CompactPriv>)
     @extends gtk::Widget;
 }
 
+/// Part of the format shown by its own widget
+enum Segment {
+    /// a number, every character is a `RollingChar` so it's animated
+    Digits(Item<'static>, gtk::Box),
+    /// everything else, like the weekday, AM/PM or the separators
+    Text(Item<'static>, gtk::Label),
+}
+
 #[derive(CompositeTemplate, Properties)]
 #[properties(wrapper_type = Compact)]
 #[template(resource = "/com/github/cr3eperall/dynislandModules/clockModule/compact.ui")]
 pub struct CompactPriv {
     #[template_child]
-    pub hour_dec: TemplateChild<RollingChar>,
-    #[template_child]
-    pub hour_unit: TemplateChild<RollingChar>,
-    #[template_child]
-    pub minute_dec: TemplateChild<RollingChar>,
-    #[template_child]
-    pub minute_unit: TemplateChild<RollingChar>,
-    #[property(get, set, default_value = true)]
+    pub segments_box: TemplateChild<gtk::Box>,
+    #[property(get, set = Self::set_format_24h, default_value = true)]
     format_24h: RefCell<bool>,
+    /// chrono strftime format, if it's empty `format_24h` chooses the default one
+    #[property(get, set = Self::set_format)]
+    format: RefCell<String>,
+    /// `None` for the local timezone
+    timezone: RefCell<Option<Tz>>,
+    time: RefCell<chrono::DateTime<Local>>,
+    segments: RefCell<Vec<Segment>>,
 }
 
 impl Default for CompactPriv {
     fn default() -> Self {
         Self {
-            hour_dec: Default::default(),
-            hour_unit: Default::default(),
-            minute_dec: Default::default(),
-            minute_unit: Default::default(),
+            segments_box: Default::default(),
             format_24h: RefCell::new(true),
+            format: RefCell::new(String::new()),
+            timezone: RefCell::new(None),
+            time: RefCell::new(Local::now()),
+            segments: RefCell::new(Vec::new()),
         }
     }
 }
@@ -71,6 +91,7 @@ impl ObjectSubclass for CompactPriv {
 impl ObjectImpl for CompactPriv {
     fn constructed(&self) {
         self.parent_constructed();
+        self.build_segments();
     }
     fn dispose(&self) {
         while let Some(child) = self.obj().first_child() {
@@ -82,6 +103,88 @@ impl ObjectImpl for CompactPriv {
 
 impl WidgetImpl for CompactPriv {}
 
+impl CompactPriv {
+    fn set_format_24h(&self, format_24h: bool) {
+        if self.format_24h.replace(format_24h) != format_24h {
+            self.build_segments();
+        }
+    }
+
+    fn set_format(&self, format: String) {
+        if *self.format.borrow() == format {
+            return;
+        }
+        self.format.replace(format);
+        self.build_segments();
+    }
+
+    /// Replaces the widgets of the segments with the ones of the current format
+    fn build_segments(&self) {
+        while let Some(child) = self.segments_box.first_child() {
+            self.segments_box.remove(&child);
+        }
+        let mut segments = self.segments.borrow_mut();
+        segments.clear();
+        for item in parse_format(&self.format.borrow(), *self.format_24h.borrow()) {
+            let segment = if is_digits(&item) {
+                let digits = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+                digits.add_css_class("digits");
+                self.segments_box.append(&digits);
+                Segment::Digits(item, digits)
+            } else {
+                let label = gtk::Label::new(None);
+                label.add_css_class("text");
+                self.segments_box.append(&label);
+                Segment::Text(item, label)
+            };
+            segments.push(segment);
+        }
+        drop(segments);
+        self.update_segments();
+    }
+
+    fn update_segments(&self) {
+        let time = *self.time.borrow();
+        let timezone = *self.timezone.borrow();
+        for segment in self.segments.borrow().iter() {
+            match segment {
+                Segment::Digits(item, digits) => {
+                    let text = format_in(&time, timezone, std::slice::from_ref(item));
+                    let mut chars = Vec::new();
+                    let mut child = digits.first_child();
+                    while let Some(widget) = child {
+                        child = widget.next_sibling();
+                        chars.push(widget);
+                    }
+                    // a number without padding can change its length
+                    if chars.len() != text.chars().count() {
+                        for widget in chars.drain(..) {
+                            digits.remove(&widget);
+                        }
+                        for _ in text.chars() {
+                            let rolling_char: RollingChar = Object::builder().build();
+                            rolling_char.set_valign(gtk::Align::Center);
+                            digits.append(&rolling_char);
+                            chars.push(rolling_char.upcast());
+                        }
+                    }
+                    for (widget, char) in chars.iter().zip(text.chars()) {
+                        if let Some(rolling_char) = widget.downcast_ref::<RollingChar>() {
+                            rolling_char.set_current_char(char);
+                        }
+                    }
+                }
+                Segment::Text(item, label) => {
+                    let text = format_in(&time, timezone, std::slice::from_ref(item));
+                    if label.label() != text {
+                        label.set_label(&text);
+                    }
+                }
+            }
+        }
+    }
+}
+
 impl Compact {
     pub fn new(dynamic_activity: &mut DynamicActivity) -> Self {
         let this: Self = Object::builder().build();
@@ -103,28 +206,90 @@ impl Compact {
         this
     }
 
+    /// `None` for the local timezone, it's used from the next time update
+    pub fn set_timezone(&self, timezone: Option<Tz>) {
+        self.imp().timezone.replace(timezone);
+    }
+
     /// must be used from the gtk main context
     pub fn set_time(&self, time: chrono::DateTime<Local>) {
         let imp = self.imp();
+        imp.time.replace(time);
+        imp.update_segments();
+    }
+}
+
+/// Returns the items of `format`, or of the default format if it's empty or invalid
+fn parse_format(format: &str, format_24h: bool) -> Vec<Item<'static>> {
+    if !format.is_empty() {
+        let items: Vec<Item<'static>> = StrftimeItems::new(format)
+            .map(|item| item.to_owned())
+            .collect();
+        if !items.contains(&Item::Error) {
+            return items;
+        }
+        log::warn!("invalid clock format: {format}, using the default one");
+    }
+    let default_format = if format_24h { "%k:%M" } else { "%l:%M" };
+    StrftimeItems::new(default_format)
+        .map(|item| item.to_owned())
+        .collect()
+}
+
+/// Returns true if `item` is a number, shown by a [`Segment::Digits`]
+fn is_digits(item: &Item) -> bool {
+    matches!(item, Item::Numeric(_, _))
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{DateTime, TimeZone, Utc};
+
+    use super::*;
+
+    fn digits(format: &str) -> Vec<bool> {
+        parse_format(format, true).iter().map(is_digits).collect()
+    }
+
+    fn utc(day: u32, hour: u32) -> DateTime<Local> {
+        Utc.with_ymd_and_hms(2024, 1, day, hour, 5, 9)
+            .unwrap()
+            .with_timezone(&Local)
+    }
+
+    #[test]
+    fn segments() {
+        assert_eq!(digits("%H:%M:%S"), [true, false, true, false, true]);
+        assert_eq!(digits("%a %-d %b"), [false, false, true, false, false]);
+        assert_eq!(digits("%l:%M %p"), [true, false, true, false, false]);
+        assert_eq!(digits("%s"), [true]);
+    }
+
+    #[test]
+    fn default_format() {
+        assert_eq!(digits(""), [true, false, true]);
+        // an invalid format uses the default one
+        assert_eq!(digits("%Q"), [true, false, true]);
+        let format = |format_24h| {
+            let items = parse_format("", format_24h);
+            format_in(&utc(1, 15), Some(Tz::UTC), &items)
+        };
+        assert_eq!(format(true), "15:05");
+        assert_eq!(format(false), " 3:05");
+    }
 
-        let hour = if self.format_24h() {
-            time.hour()
-        } else {
-            time.hour12().1
+    #[test]
+    fn variable_width_digits() {
+        let text = |format: &str, time| {
+            let items = parse_format(format, true);
+            let digits = items.iter().find(|item| is_digits(item)).unwrap();
+            format_in(&time, Some(Tz::UTC), std::slice::from_ref(digits))
         };
-        let hour = format!("{:>2}", hour);
-        let (hour_dec, hour_unit) = (hour.chars().nth(0).unwrap(), hour.chars().nth(1).unwrap());
-
-        let minute = time.minute();
-        let minute = format!("{:0>2}", minute);
-        let (minute_dec, minute_unit) = (
-            minute.chars().nth(0).unwrap(),
-            minute.chars().nth(1).unwrap(),
-        );
-
-        imp.hour_dec.set_current_char(hour_dec);
-        imp.hour_unit.set_current_char(hour_unit);
-        imp.minute_dec.set_current_char(minute_dec);
-        imp.minute_unit.set_current_char(minute_unit);
+        assert_eq!(text("%-d", utc(5, 12)), "5");
+        assert_eq!(text("%-d", utc(15, 12)), "15");
+        // %l pads with a space instead of a zero
+        assert_eq!(text("%l", utc(1, 9)), " 9");
+        assert_eq!(text("%l", utc(1, 22)), "10");
+        assert_eq!(text("%s", utc(1, 0)), "1704067509");
     }
 }
@@ -0,0 +1,194 @@
+use std::cell::RefCell;
+
+use chrono::{Local, Timelike};
+use chrono_tz::Tz;
+use dynisland_core::{
+    abi::{glib, gtk},
+    cast_dyn_any,
+    dynamic_activity::DynamicActivity,
+};
+use glib::{
+    subclass::{
+        object::{DerivedObjectProperties, ObjectImpl, ObjectImplExt},
+        types::{ObjectSubclass, ObjectSubclassExt, ObjectSubclassIsExt},
+    },
+    Object, Properties,
+};
+use gtk::{
+    prelude::*,
+    subclass::widget::{WidgetClassExt, WidgetImpl},
+    BinLayout,
+};
+
+use crate::timezone::{city_name, format_offset, is_day, offset_in, time_in};
+
+glib::wrapper! {
+    pub struct WorldClock(ObjectSubclass<WorldClockPriv>)
+    @extends gtk::Widget;
+}
+
+/// A row of the list, the labels are updated every time the time changes
+struct ZoneRow {
+    timezone: Tz,
+    row: gtk::Box,
+    time: gtk::Label,
+    offset: gtk::Label,
+    day_night: gtk::Image,
+}
+
+#[derive(Properties)]
+#[properties(wrapper_type = WorldClock)]
+pub struct WorldClockPriv {
+    #[property(get, set = Self::set_format_24h, default_value = true)]
+    format_24h: RefCell<bool>,
+    list: gtk::Box,
+    rows: RefCell<Vec<ZoneRow>>,
+    time: RefCell<chrono::DateTime<Local>>,
+}
+
+impl Default for WorldClockPriv {
+    fn default() -> Self {
+        Self {
+            format_24h: RefCell::new(true),
+            list: gtk::Box::new(gtk::Orientation::Vertical, 4),
+            rows: RefCell::new(Vec::new()),
+            time: RefCell::new(Local::now()),
+        }
+    }
+}
+
+#[glib::object_subclass]
+impl ObjectSubclass for WorldClockPriv {
+    const NAME: &'static str = "ClockExpandedWidget";
+    type Type = WorldClock;
+    type ParentType = gtk::Widget;
+
+    fn class_init(klass: &mut Self::Class) {
+        klass.set_layout_manager_type::<BinLayout>();
+    }
+}
+
+#[glib::derived_properties]
+impl ObjectImpl for WorldClockPriv {
+    fn constructed(&self) {
+        self.parent_constructed();
+        self.list.set_valign(gtk::Align::Center);
+        self.list.set_halign(gtk::Align::Center);
+        self.list.set_parent(&*self.obj());
+    }
+    fn dispose(&self) {
+        while let Some(child) = self.obj().first_child() {
+            child.unparent();
+        }
+    }
+}
+
+impl WidgetImpl for WorldClockPriv {}
+
+impl WorldClockPriv {
+    fn set_format_24h(&self, format_24h: bool) {
+        if self.format_24h.replace(format_24h) != format_24h {
+            self.obj().update_rows();
+        }
+    }
+}
+
+impl WorldClock {
+    /// registered properties:
+    pub fn new(dynamic_activity: &mut DynamicActivity) -> Self {
+        let this: Self = Object::builder().build();
+        this.add_css_class("world-clock");
+        let world_clock = this.clone();
+        if dynamic_activity.get_property_any("time").is_err() {
+            dynamic_activity
+                .add_dynamic_property("time", Local::now())
+                .unwrap();
+        }
+        dynamic_activity
+            .subscribe_to_property("time", move |value| {
+                let time = cast_dyn_any!(value, chrono::DateTime<chrono::Local>).unwrap();
+                world_clock.set_time(*time);
+            })
+            .unwrap();
+
+        this
+    }
+
+    /// Replaces the listed timezones, `timezones` are the names with the parsed timezone
+    pub fn set_timezones(&self, timezones: Vec<(String, Tz)>) {
+        let imp = self.imp();
+        while let Some(child) = imp.list.first_child() {
+            imp.list.remove(&child);
+        }
+        let mut rows = imp.rows.borrow_mut();
+        rows.clear();
+        for (name, timezone) in timezones {
+            let row = gtk::Box::new(gtk::Orientation::Horizontal, 8);
+            row.add_css_class("zone-row");
+            let day_night = gtk::Image::new();
+            day_night.add_css_class("day-night");
+            let city = gtk::Label::new(Some(&city_name(&name)));
+            city.add_css_class("city");
+            city.set_hexpand(true);
+            city.set_xalign(0.0);
+            let offset = gtk::Label::new(None);
+            offset.add_css_class("offset");
+            let time = gtk::Label::new(None);
+            time.add_css_class("time");
+            row.append(&day_night);
+            row.append(&city);
+            row.append(&offset);
+            row.append(&time);
+            imp.list.append(&row);
+            rows.push(ZoneRow {
+                timezone,
+                row,
+                time,
+                offset,
+                day_night,
+            });
+        }
+        drop(rows);
+        self.update_rows();
+    }
+
+    pub fn has_timezones(&self) -> bool {
+        !self.imp().rows.borrow().is_empty()
+    }
+
+    /// must be used from the gtk main context
+    pub fn set_time(&self, time: chrono::DateTime<Local>) {
+        let old_time = self.imp().time.replace(time);
+        if time.minute() != old_time.minute() {
+            self.update_rows();
+        }
+    }
+
+    fn update_rows(&self) {
+        let imp = self.imp();
+        let now = *imp.time.borrow();
+        for zone in imp.rows.borrow().iter() {
+            let time = time_in(&now, Some(zone.timezone));
+            let text = if self.format_24h() {
+                format!("{:0>2}:{:0>2}", time.hour(), time.minute())
+            } else {
+                let (pm, hour) = time.hour12();
+                let suffix = if pm { "PM" } else { "AM" };
+                format!("{}:{:0>2} {suffix}", hour, time.minute())
+            };
+            zone.time.set_label(&text);
+            zone.offset
+                .set_label(&format_offset(offset_in(&now, zone.timezone)));
+            if is_day(&time) {
+                zone.day_night.set_icon_name(Some("weather-clear-symbolic"));
+                zone.row.remove_css_class("night");
+                zone.row.add_css_class("day");
+            } else {
+                zone.day_night
+                    .set_icon_name(Some("weather-clear-night-symbolic"));
+                zone.row.remove_css_class("day");
+                zone.row.add_css_class("night");
+            }
+        }
+    }
+}
@@ -1,15 +1,18 @@
 use clock::Clock;
 use compact::Compact;
 use dynisland_core::{
-    abi::{gdk, gtk},
+    abi::{gdk, glib, gtk},
     dynamic_activity::DynamicActivity,
     dynamic_property::PropertyUpdate,
     graphics::activity_widget::{boxed_activity_mode::ActivityMode, ActivityWidget},
 };
+use expanded::WorldClock;
+use glib::object::CastNone;
 use gtk::{prelude::*, GestureClick};
 
 pub mod clock;
 pub mod compact;
+pub mod expanded;
 
 pub fn get_activity(
     prop_send: tokio::sync::mpsc::UnboundedSender<PropertyUpdate>,
@@ -36,8 +39,11 @@ pub fn get_activity(
 
     let compact = Compact::new(&mut dynamic_act);
 
+    let expanded = WorldClock::new(&mut dynamic_act);
+
     activity_widget.set_minimal_mode_widget(minimal);
     activity_widget.set_compact_mode_widget(compact);
+    activity_widget.set_expanded_mode_widget(expanded);
 
     register_mode_gestures(activity_widget);
 
@@ -45,30 +51,29 @@ pub fn get_activity(
 }
 
 fn register_mode_gestures(activity_widget: ActivityWidget) {
-    // let primary_gesture = gtk::GestureClick::new();
-    // primary_gesture.set_button(gdk::BUTTON_PRIMARY);
+    let primary_gesture = gtk::GestureClick::new();
+    primary_gesture.set_button(gdk::BUTTON_PRIMARY);
 
-    // primary_gesture.connect_released(move |gest, _, x, y| {
-    //     let aw = gest.widget().downcast::<ActivityWidget>().unwrap();
-    //     if x < 0.0
-    //         || y < 0.0
-    //         || x > aw.size(gtk::Orientation::Horizontal).into()
-    //         || y > aw.size(gtk::Orientation::Vertical).into()
-    //     {
-    //         return;
-    //     }
-    //     match aw.mode() {
-    //         ActivityMode::Compact => {
-    //             aw.set_mode(ActivityMode::Expanded);
-    //         }
-    //         ActivityMode::Expanded => {
-    //             aw.set_mode(ActivityMode::Overlay);
-    //         }
-    //         _ => {}
-    //     }
-    // });
+    primary_gesture.connect_released(move |gest, _, x, y| {
+        let aw = gest.widget().downcast::<ActivityWidget>().unwrap();
+        if x < 0.0
+            || y < 0.0
+            || x > aw.size(gtk::Orientation::Horizontal).into()
+            || y > aw.size(gtk::Orientation::Vertical).into()
+        {
+            return;
+        }
+        // the world clock is empty without timezones
+        let has_timezones = aw
+            .expanded_mode_widget()
+            .and_downcast::<WorldClock>()
+            .is_some_and(|world_clock| world_clock.has_timezones());
+        if aw.mode() == ActivityMode::Compact && has_timezones {
+            aw.set_mode(ActivityMode::Expanded);
+        }
+    });
 
-    // activity_widget.add_controller(primary_gesture);
+    activity_widget.add_controller(primary_gesture);
 
     let secondary_gesture = GestureClick::new();
     secondary_gesture.set_button(gdk::BUTTON_SECONDARY);
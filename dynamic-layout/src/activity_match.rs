@@ -0,0 +1,312 @@
+use std::{
+    fmt::{Display, Formatter},
+    str::FromStr,
+};
+
+use dynisland_core::abi::module::ActivityIdentifier;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+
+/// A rule of `activity_order`:
+///
+/// `[!]<module>` or `[!]<activity>@<module>`, optionally followed by `[key=value, ...]` to match
+/// the metadata of the activity (`window` is the window requested by the module).
+///
+/// Every name can be a glob (`*` and `?`) or a regex between slashes (`/script-[0-9]+/`, a `/`
+/// in the regex is written `\/`), names are case insensitive.
+#[derive(Debug, Clone)]
+pub enum ActivityMatch {
+    Rule {
+        negated: bool,
+        activity: Option<Pattern>,
+        module: Pattern,
+        metadata: Vec<(String, Pattern)>,
+    },
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub enum Pattern {
+    Glob(String),
+    /// the regex is anchored to match the whole name, `source` is the one written in the config
+    Regex {
+        source: String,
+        regex: Regex,
+    },
+}
+
+impl Pattern {
+    pub fn matches(&self, text: &str) -> bool {
+        match self {
+            Pattern::Glob(glob) => glob_match(&glob.to_lowercase(), &text.to_lowercase()),
+            Pattern::Regex { regex, .. } => regex.is_match(text),
+        }
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Glob(glob) => write!(f, "{}", glob),
+            Pattern::Regex { source, .. } => write!(f, "/{}/", source),
+        }
+    }
+}
+
+impl FromStr for Pattern {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        if s.is_empty() {
+            return Err(String::from("empty name"));
+        }
+        if s.len() >= 2 && s.starts_with('/') && s.ends_with('/') {
+            let source = &s[1..s.len() - 1];
+            return RegexBuilder::new(&format!("^(?:{source})$"))
+                .case_insensitive(true)
+                .build()
+                .map(|regex| Pattern::Regex {
+                    source: source.to_string(),
+                    regex,
+                })
+                .map_err(|err| format!("invalid regex {source}: {err}"));
+        }
+        Ok(Pattern::Glob(s.to_string()))
+    }
+}
+
+impl Display for ActivityMatch {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ActivityMatch::Rule {
+                negated,
+                activity,
+                module,
+                metadata,
+            } => {
+                if *negated {
+                    write!(f, "!")?;
+                }
+                if let Some(activity) = activity {
+                    write!(f, "{}@", activity)?;
+                }
+                write!(f, "{}", module)?;
+                if !metadata.is_empty() {
+                    let metadata = metadata
+                        .iter()
+                        .map(|(key, value)| format!("{key}={value}"))
+                        .collect::<Vec<String>>()
+                        .join(",");
+                    write!(f, "[{}]", metadata)?;
+                }
+                Ok(())
+            }
+            ActivityMatch::Other => write!(f, "*"),
+        }
+    }
+}
+impl Serialize for ActivityMatch {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let disp = self.to_string();
+        serializer.serialize_str(&disp)
+    }
+}
+impl FromStr for ActivityMatch {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (negated, s) = match s.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, s),
+        };
+        let (target, metadata) = match split_outside_regex(s, '[') {
+            Some((target, metadata)) => {
+                let metadata = metadata
+                    .strip_suffix(']')
+                    .ok_or_else(|| String::from("missing ] at the end of the metadata"))?;
+                (target, parse_metadata(metadata)?)
+            }
+            None => (s, Vec::new()),
+        };
+        let (activity, module) = match split_outside_regex(target, '@') {
+            Some((activity, module)) => (Some(Pattern::from_str(activity)?), module),
+            None => (None, target),
+        };
+        if split_outside_regex(module, '@').is_some() {
+            return Err(String::from("invalid match, too many @"));
+        }
+        Ok(ActivityMatch::Rule {
+            negated,
+            activity,
+            module: Pattern::from_str(module)?,
+            metadata,
+        })
+    }
+}
+impl ActivityMatch {
+    pub(crate) fn contains(&self, id: &ActivityIdentifier) -> bool {
+        match self {
+            ActivityMatch::Rule {
+                negated,
+                activity,
+                module,
+                metadata,
+            } => {
+                let activity_matches = match activity {
+                    Some(activity) => activity.matches(&id.activity()),
+                    None => true,
+                };
+                let metadata_matches = metadata.iter().all(|(key, value)| {
+                    let actual = if key == "window" {
+                        id.metadata().window_name()
+                    } else {
+                        id.metadata().additional_metadata(key)
+                    };
+                    match actual {
+                        Some(actual) => value.matches(&actual),
+                        None => false,
+                    }
+                });
+                let matches = module.matches(&id.module()) && activity_matches && metadata_matches;
+                matches != *negated
+            }
+            ActivityMatch::Other => true,
+        }
+    }
+}
+
+/// Splits `s` at the first `separator` that is not inside a `/regex/`,
+/// `\/` doesn't end the regex
+fn split_outside_regex(s: &str, separator: char) -> Option<(&str, &str)> {
+    let mut in_regex = false;
+    let mut escaped = false;
+    for (idx, c) in s.char_indices() {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' && in_regex {
+            escaped = true;
+        } else if c == '/' {
+            in_regex = !in_regex;
+        } else if c == separator && !in_regex {
+            return Some((&s[..idx], &s[idx + separator.len_utf8()..]));
+        }
+    }
+    None
+}
+
+fn parse_metadata(metadata: &str) -> Result<Vec<(String, Pattern)>, String> {
+    let mut result = Vec::new();
+    let mut rest = metadata;
+    while !rest.trim().is_empty() {
+        let (entry, next) = match split_outside_regex(rest, ',') {
+            Some((entry, next)) => (entry, next),
+            None => (rest, ""),
+        };
+        let (key, value) = entry
+            .split_once('=')
+            .ok_or_else(|| format!("invalid metadata {entry}, use key=value"))?;
+        let key = key.trim();
+        if key.is_empty() {
+            return Err(format!("invalid metadata {entry}, empty key"));
+        }
+        result.push((key.to_string(), Pattern::from_str(value)?));
+        rest = next;
+    }
+    Ok(result)
+}
+
+/// Matches `text` against a glob with `*` (any sequence) and `?` (any character)
+fn glob_match(glob: &str, text: &str) -> bool {
+    let glob: Vec<char> = glob.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut g, mut t) = (0, 0);
+    // position of the last * in the glob and of the text when it was found
+    let mut backtrack: Option<(usize, usize)> = None;
+    while t < text.len() {
+        if g < glob.len() && (glob[g] == '?' || glob[g] == text[t]) {
+            g += 1;
+            t += 1;
+        } else if g < glob.len() && glob[g] == '*' {
+            backtrack = Some((g, t));
+            g += 1;
+        } else if let Some((star, star_t)) = backtrack {
+            g = star + 1;
+            t = star_t + 1;
+            backtrack = Some((star, star_t + 1));
+        } else {
+            return false;
+        }
+    }
+    glob[g..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn glob() {
+        assert!(glob_match("*", ""));
+        assert!(glob_match("script*", "script-1"));
+        assert!(glob_match("s?ript", "script"));
+        assert!(glob_match("*-*-end", "a-b-c-end"));
+        assert!(!glob_match("script", "scripts"));
+        assert!(!glob_match("?", ""));
+        assert!(!glob_match("*a", "bab"));
+    }
+
+    #[test]
+    fn pattern() {
+        assert!(Pattern::from_str("Script*")
+            .unwrap()
+            .matches("scriptModule"));
+        let regex = Pattern::from_str("/script-[0-9]+/").unwrap();
+        assert!(regex.matches("Script-12"));
+        // the regex matches the whole name
+        assert!(!regex.matches("script-12a"));
+        assert!(Pattern::from_str(" ").is_err());
+        assert!(Pattern::from_str("/[/").is_err());
+    }
+
+    #[test]
+    fn split() {
+        assert_eq!(split_outside_regex("a@b", '@'), Some(("a", "b")));
+        assert_eq!(split_outside_regex("/a@b/@c", '@'), Some(("/a@b/", "c")));
+        assert_eq!(
+            split_outside_regex("/a\\/@b/@c", '@'),
+            Some(("/a\\/@b/", "c"))
+        );
+        assert_eq!(split_outside_regex("abc", '@'), None);
+    }
+
+    #[test]
+    fn parse_rules() {
+        let rules = [
+            "ScriptModule",
+            "!*@ScriptModule",
+            "/music-[a-z]+/@MusicModule",
+            "/a\\/b/@Module",
+            "*@Module[window=main,player=/spot.*/]",
+        ];
+        for rule in rules {
+            assert_eq!(ActivityMatch::from_str(rule).unwrap().to_string(), rule);
+        }
+        let rule = ActivityMatch::from_str("/a\\/b/@Module").unwrap();
+        match rule {
+            ActivityMatch::Rule {
+                activity: Some(activity),
+                ..
+            } => assert!(activity.matches("a/b")),
+            _ => panic!("expected a rule with an activity"),
+        }
+        assert!(ActivityMatch::from_str("a@b@c").is_err());
+        assert!(ActivityMatch::from_str("Module[window=main").is_err());
+        assert!(ActivityMatch::from_str("Module[window]").is_err());
+        assert!(ActivityMatch::from_str("Module[=main]").is_err());
+    }
+}
@@ -1,21 +1,24 @@
-use std::{
-    collections::HashMap,
-    fmt::{Display, Formatter},
-    str::FromStr,
-    time::Duration,
-};
+use std::{cell::RefCell, collections::HashMap, rc::Rc, str::FromStr, time::Duration};
 
 use dynisland_core::{
-    abi::{gdk, glib, gtk, log, module::ActivityIdentifier},
+    abi::{gdk, glib, glib::SourceId, gtk, log, module::ActivityIdentifier},
     d_macro::OptDeserializeConfig,
     graphics::activity_widget::{boxed_activity_mode::ActivityMode, ActivityWidget},
 };
 use gtk::{prelude::*, EventController, StateFlags};
 use serde::{Deserialize, Serialize};
+use tokio::sync::mpsc::UnboundedSender;
 
 use crate::{
+    activity_match::ActivityMatch,
+    fullscreen::FullscreenMode,
     layout::DynamicLayout,
-    window_position::{Alignment, Layer},
+    priority_order::OrderStrategy,
+    profile::{ProfileRule, RESERVED_PROFILE_NAMES},
+    window_position::{
+        Alignment, ExclusiveZone, KeyboardMode, Layer, MonitorChoice, MonitorConfig,
+        MonitorSelector, Orientation, UnmatchedMonitor,
+    },
 };
 
 // TODO: cleanup
@@ -24,15 +27,27 @@ use crate::{
 #[serde(default)]
 pub struct DynamicLayoutConfigMain {
     pub(crate) window_position: WindowPosition,
+    pub(crate) orientation: Orientation,
     pub(crate) auto_minimize_timeout: i32,
     pub(crate) max_activities: u16,
     pub(crate) max_active: u16,
     pub(crate) reorder_on_add: bool,
     pub(crate) reorder_on_reload: bool,
+    pub(crate) order_strategy: OrderStrategy,
+    pub(crate) promotion_decay: u64,
+    pub(crate) update_promotion_interval: i32,
+    pub(crate) expanded_popup: bool,
+    pub(crate) swipe_sensitivity: f64,
+    pub(crate) max_notifications: u16,
+    pub(crate) grid_columns: u16,
+    pub(crate) drag_reorder: bool,
+    pub(crate) idle_collapse_timeout: i32,
+    pub(crate) fullscreen: FullscreenMode,
     pub(crate) windows: HashMap<String, DynamicLayoutConfig>,
 }
 
 pub const DEFAULT_AUTO_MINIMIZE_TIMEOUT: i32 = 5000;
+pub const DEFAULT_PROMOTION_DECAY: u64 = 30000;
 
 impl Default for DynamicLayoutConfigMain {
     fn default() -> Self {
@@ -40,11 +55,22 @@ impl Default for DynamicLayoutConfigMain {
         map.insert("".to_string(), DynamicLayoutConfig::default());
         Self {
             window_position: WindowPosition::default(),
+            orientation: Orientation::Horizontal,
             auto_minimize_timeout: DEFAULT_AUTO_MINIMIZE_TIMEOUT,
             max_activities: 3,
             max_active: 1,
             reorder_on_add: true,
             reorder_on_reload: true,
+            order_strategy: OrderStrategy::Cycle,
+            promotion_decay: DEFAULT_PROMOTION_DECAY,
+            update_promotion_interval: -1,
+            expanded_popup: false,
+            swipe_sensitivity: 1.0,
+            max_notifications: 0,
+            grid_columns: 0,
+            drag_reorder: false,
+            idle_collapse_timeout: -1,
+            fullscreen: FullscreenMode::None,
             windows: map,
         }
     }
@@ -54,20 +80,106 @@ impl DynamicLayoutConfigMain {
     pub fn default_conf(&self) -> DynamicLayoutConfig {
         DynamicLayoutConfig {
             window_position: self.window_position.clone(),
+            orientation: self.orientation,
             auto_minimize_timeout: self.auto_minimize_timeout,
             max_activities: self.max_activities,
             max_active: self.max_active,
             reorder_on_add: self.reorder_on_add,
             reorder_on_reload: self.reorder_on_reload,
+            order_strategy: self.order_strategy,
+            promotion_decay: self.promotion_decay,
+            update_promotion_interval: self.update_promotion_interval,
+            expanded_popup: self.expanded_popup,
+            swipe_sensitivity: self.swipe_sensitivity,
+            max_notifications: self.max_notifications,
+            grid_columns: self.grid_columns,
+            drag_reorder: self.drag_reorder,
+            idle_collapse_timeout: self.idle_collapse_timeout,
+            fullscreen: self.fullscreen,
             activity_order: Vec::new(),
         }
     }
+    /// `window` is the name of a window in the config,
+    /// use `DynamicLayout::config_for_window` for the windows created for each monitor
     pub fn get_for_window(&self, window: &str) -> DynamicLayoutConfig {
         match self.windows.get(window) {
             Some(conf) => conf.clone(),
             None => self.default_conf(),
         }
     }
+
+    /// Returns the windows that should exist with the connected monitors.
+    ///
+    /// A window with more than one monitor is created once for each of them,
+    /// the first one keeps the name of the window and the others are named `name:CONNECTOR`.
+    pub fn window_instances(&self) -> Vec<WindowInstance> {
+        let mut instances = Vec::new();
+        for (name, config) in self.windows.iter() {
+            let (choice, unmatched) = config.window_position.resolve_monitors();
+            let monitors = match choice {
+                MonitorChoice::Monitors(monitors) => monitors,
+                choice => {
+                    instances.push(WindowInstance {
+                        name: name.clone(),
+                        config_name: name.clone(),
+                        monitor: None,
+                        hidden: matches!(choice, MonitorChoice::Hidden),
+                        unmatched,
+                    });
+                    continue;
+                }
+            };
+            let mut monitors = monitors.into_iter();
+            instances.push(WindowInstance {
+                name: name.clone(),
+                config_name: name.clone(),
+                monitor: monitors.next(),
+                hidden: false,
+                unmatched,
+            });
+            if !config.window_position.monitor.is_multi() {
+                continue;
+            }
+            for monitor in monitors {
+                let connector = monitor.connector().unwrap_or_default();
+                instances.push(WindowInstance {
+                    name: format!("{name}:{connector}"),
+                    config_name: name.clone(),
+                    monitor: Some(monitor),
+                    hidden: false,
+                    unmatched: false,
+                });
+            }
+        }
+        instances
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct WindowInstance {
+    pub(crate) name: String,
+    /// name of the window in the config
+    pub(crate) config_name: String,
+    /// `None` if the compositor should choose the monitor
+    pub(crate) monitor: Option<gdk::Monitor>,
+    /// none of the monitors is connected and the window should be hidden
+    pub(crate) hidden: bool,
+    /// none of the monitors is connected and `unmatched_monitor` was used
+    pub(crate) unmatched: bool,
+}
+
+impl WindowInstance {
+    /// Hides the window if it has no monitor and shows it again when it has one,
+    /// windows hidden by the user are left hidden
+    pub fn update_visibility(&self, window: &gtk::Window) {
+        if self.hidden {
+            window.add_css_class("no-monitor");
+            window.set_visible(false);
+        } else if window.has_css_class("no-monitor") {
+            window.remove_css_class("no-monitor");
+            window.present();
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Clone)]
@@ -76,6 +188,8 @@ pub struct DynamicLayoutConfig {
     #[serde(skip_serializing)]
     pub(crate) window_position: WindowPosition,
     #[serde(skip_serializing)]
+    pub(crate) orientation: Orientation,
+    #[serde(skip_serializing)]
     pub(crate) auto_minimize_timeout: i32,
     #[serde(skip_serializing)]
     pub(crate) max_activities: u16,
@@ -85,17 +199,48 @@ pub struct DynamicLayoutConfig {
     pub(crate) reorder_on_add: bool,
     #[serde(skip_serializing)]
     pub(crate) reorder_on_reload: bool,
+    #[serde(skip_serializing)]
+    pub(crate) order_strategy: OrderStrategy,
+    #[serde(skip_serializing)]
+    pub(crate) promotion_decay: u64,
+    #[serde(skip_serializing)]
+    pub(crate) update_promotion_interval: i32,
+    #[serde(skip_serializing)]
+    pub(crate) expanded_popup: bool,
+    #[serde(skip_serializing)]
+    pub(crate) swipe_sensitivity: f64,
+    #[serde(skip_serializing)]
+    pub(crate) max_notifications: u16,
+    #[serde(skip_serializing)]
+    pub(crate) grid_columns: u16,
+    #[serde(skip_serializing)]
+    pub(crate) drag_reorder: bool,
+    #[serde(skip_serializing)]
+    pub(crate) idle_collapse_timeout: i32,
+    #[serde(skip_serializing)]
+    pub(crate) fullscreen: FullscreenMode,
     pub(crate) activity_order: Vec<ActivityMatch>,
 }
 impl Default for DynamicLayoutConfig {
     fn default() -> Self {
         Self {
             window_position: WindowPosition::default(),
+            orientation: Orientation::Horizontal,
             auto_minimize_timeout: DEFAULT_AUTO_MINIMIZE_TIMEOUT,
             max_activities: 3,
             max_active: 1,
             reorder_on_add: true,
             reorder_on_reload: true,
+            order_strategy: OrderStrategy::Cycle,
+            promotion_decay: DEFAULT_PROMOTION_DECAY,
+            update_promotion_interval: -1,
+            expanded_popup: false,
+            swipe_sensitivity: 1.0,
+            max_notifications: 0,
+            grid_columns: 0,
+            drag_reorder: false,
+            idle_collapse_timeout: -1,
+            fullscreen: FullscreenMode::None,
             activity_order: Vec::new(),
         }
     }
@@ -105,38 +250,196 @@ impl Default for DynamicLayoutConfig {
 #[serde(default)]
 pub struct DeDynamicLayoutConfigMain {
     window_position: WindowPosition,
+    orientation: Orientation,
     auto_minimize_timeout: i32,
     max_activities: u16,
     max_active: u16,
     reorder_on_add: bool,
     reorder_on_reload: bool,
+    order_strategy: OrderStrategy,
+    promotion_decay: u64,
+    update_promotion_interval: i32,
+    expanded_popup: bool,
+    swipe_sensitivity: f64,
+    max_notifications: u16,
+    grid_columns: u16,
+    drag_reorder: bool,
+    idle_collapse_timeout: i32,
+    fullscreen: FullscreenMode,
     windows: HashMap<String, DeDynamicLayoutConfig>,
+    /// profile name -> windows with the values that replace the ones in `windows`
+    pub(crate) profiles: HashMap<String, HashMap<String, DeDynamicLayoutConfig>>,
+    pub(crate) profile_rules: Vec<ProfileRule>,
 }
 
 impl Default for DeDynamicLayoutConfigMain {
     fn default() -> Self {
         Self {
             window_position: WindowPosition::default(),
+            orientation: Orientation::Horizontal,
             auto_minimize_timeout: DEFAULT_AUTO_MINIMIZE_TIMEOUT,
             max_activities: 3,
             max_active: 1,
             reorder_on_add: true,
             reorder_on_reload: true,
+            order_strategy: OrderStrategy::Cycle,
+            promotion_decay: DEFAULT_PROMOTION_DECAY,
+            update_promotion_interval: -1,
+            expanded_popup: false,
+            swipe_sensitivity: 1.0,
+            max_notifications: 0,
+            grid_columns: 0,
+            drag_reorder: false,
+            idle_collapse_timeout: -1,
+            fullscreen: FullscreenMode::None,
             windows: HashMap::new(),
+            profiles: HashMap::new(),
+            profile_rules: Vec::new(),
         }
     }
 }
 
 impl DeDynamicLayoutConfigMain {
+    /// Returns the config with the windows of `profile` applied over `windows`
+    pub fn with_profile(&self, profile: Option<&str>) -> Self {
+        let mut config = self.clone();
+        let profile = match profile {
+            Some(profile) => profile,
+            None => return config,
+        };
+        match self.profiles.get(profile) {
+            Some(windows) => {
+                for (name, opt_config) in windows {
+                    config
+                        .windows
+                        .entry(name.clone())
+                        .or_default()
+                        .overlay(opt_config);
+                }
+            }
+            None => log::warn!("profile {profile} not found, using the config without profiles"),
+        }
+        config
+    }
+
+    /// Returns every problem of the config, each window is checked with the values it inherits
+    /// and the windows of every profile are checked too
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = self.validate_windows();
+        let mut profiles: Vec<&String> = self.profiles.keys().collect();
+        profiles.sort();
+        for profile in profiles {
+            if RESERVED_PROFILE_NAMES.contains(&profile.as_str()) {
+                problems.push(format!(
+                    "profile \"{profile}\": the name is used by the profile command, rename it"
+                ));
+            }
+            for problem in self.with_profile(Some(profile)).validate_windows() {
+                // the windows that the profile doesn't change are already checked
+                if !problems.contains(&problem) {
+                    problems.push(format!("profile \"{profile}\", {problem}"));
+                }
+            }
+        }
+        for rule in self.profile_rules.iter() {
+            problems.append(&mut rule.validate(self.profiles.keys()));
+        }
+        problems
+    }
+
+    /// Returns the monitor selectors that match no connected monitor, in the windows and in the
+    /// profiles. They are not problems of the config, the monitor can be connected later
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = self.window_warnings();
+        let mut profiles: Vec<&String> = self.profiles.keys().collect();
+        profiles.sort();
+        for profile in profiles {
+            for warning in self.with_profile(Some(profile)).window_warnings() {
+                if !warnings.contains(&warning) {
+                    warnings.push(format!("profile \"{profile}\", {warning}"));
+                }
+            }
+        }
+        warnings
+    }
+
+    fn window_warnings(&self) -> Vec<String> {
+        let mut warnings = Vec::new();
+        let main_config = self.clone().into_main_config();
+        let mut names: Vec<&String> = main_config.windows.keys().collect();
+        names.sort();
+        for name in names {
+            let window = window_label(name);
+            for warning in main_config.windows[name]
+                .window_position
+                .unmatched_selectors()
+            {
+                warnings.push(format!("{window}: {warning}"));
+            }
+        }
+        warnings
+    }
+
+    fn validate_windows(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+        let main_config = self.clone().into_main_config();
+        let mut names: Vec<&String> = main_config.windows.keys().collect();
+        names.sort();
+        for name in names {
+            let config = &main_config.windows[name];
+            let window = window_label(name);
+            for problem in config.window_position.validate() {
+                problems.push(format!("{window}: {problem}"));
+            }
+            if config.max_active > config.max_activities {
+                problems.push(format!(
+                    "{window}: max_active ({}) is more than max_activities ({})",
+                    config.max_active, config.max_activities
+                ));
+            }
+            let timeouts = [
+                ("auto_minimize_timeout", config.auto_minimize_timeout),
+                ("idle_collapse_timeout", config.idle_collapse_timeout),
+                (
+                    "update_promotion_interval",
+                    config.update_promotion_interval,
+                ),
+            ];
+            for (option, timeout) in timeouts {
+                if timeout < -1 {
+                    problems.push(format!(
+                        "{window}: {option} is {timeout}, use -1 to disable it"
+                    ));
+                }
+            }
+            let rules = self
+                .windows
+                .get(name)
+                .and_then(|opt_config| opt_config.activity_order.as_ref());
+            for rule in rules.into_iter().flatten() {
+                if let Err(err) = ActivityMatch::from_str(rule) {
+                    problems.push(format!(
+                        "{window}: invalid activity_order rule \"{rule}\": {err}"
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
     pub fn into_main_config(self) -> DynamicLayoutConfigMain {
         let mut windows = HashMap::new();
         for (name, opt_config) in self.windows {
-            let window_pos = match opt_config.window_position {
+            let mut window_pos = match opt_config.window_position {
                 Some(opt_window_pos) => opt_window_pos.into_config(&self.window_position),
                 None => self.window_position.clone(),
             };
+            for overlay in opt_config.window_position_overlays {
+                window_pos = overlay.into_config(&window_pos);
+            }
             let conf = DynamicLayoutConfig {
                 window_position: window_pos,
+                orientation: opt_config.orientation.unwrap_or(self.orientation),
                 auto_minimize_timeout: opt_config
                     .auto_minimize_timeout
                     .unwrap_or(self.auto_minimize_timeout),
@@ -146,17 +449,46 @@ impl DeDynamicLayoutConfigMain {
                 reorder_on_reload: opt_config
                     .reorder_on_reload
                     .unwrap_or(self.reorder_on_reload),
+                order_strategy: opt_config.order_strategy.unwrap_or(self.order_strategy),
+                promotion_decay: opt_config.promotion_decay.unwrap_or(self.promotion_decay),
+                update_promotion_interval: opt_config
+                    .update_promotion_interval
+                    .unwrap_or(self.update_promotion_interval),
+                expanded_popup: opt_config.expanded_popup.unwrap_or(self.expanded_popup),
+                swipe_sensitivity: opt_config
+                    .swipe_sensitivity
+                    .unwrap_or(self.swipe_sensitivity),
+                max_notifications: opt_config
+                    .max_notifications
+                    .unwrap_or(self.max_notifications),
+                grid_columns: opt_config.grid_columns.unwrap_or(self.grid_columns),
+                drag_reorder: opt_config.drag_reorder.unwrap_or(self.drag_reorder),
+                idle_collapse_timeout: opt_config
+                    .idle_collapse_timeout
+                    .unwrap_or(self.idle_collapse_timeout),
+                fullscreen: opt_config.fullscreen.unwrap_or(self.fullscreen),
                 activity_order: DeDynamicLayoutConfig::get_order(opt_config.activity_order),
             };
             windows.insert(name, conf);
         }
         let mut main_conf = DynamicLayoutConfigMain {
             window_position: self.window_position,
+            orientation: self.orientation,
             auto_minimize_timeout: self.auto_minimize_timeout,
             max_activities: self.max_activities,
             max_active: self.max_active,
             reorder_on_add: self.reorder_on_add,
             reorder_on_reload: self.reorder_on_reload,
+            order_strategy: self.order_strategy,
+            promotion_decay: self.promotion_decay,
+            update_promotion_interval: self.update_promotion_interval,
+            expanded_popup: self.expanded_popup,
+            swipe_sensitivity: self.swipe_sensitivity,
+            max_notifications: self.max_notifications,
+            grid_columns: self.grid_columns,
+            drag_reorder: self.drag_reorder,
+            idle_collapse_timeout: self.idle_collapse_timeout,
+            fullscreen: self.fullscreen,
             windows,
         };
         if !main_conf.windows.contains_key("") {
@@ -171,76 +503,72 @@ impl DeDynamicLayoutConfigMain {
 #[serde(default)]
 pub struct DeDynamicLayoutConfig {
     window_position: Option<DeWindowPosition>,
+    /// positions of the profiles applied over `window_position`, they only replace the values
+    /// they set
+    #[serde(skip)]
+    window_position_overlays: Vec<DeWindowPosition>,
+    orientation: Option<Orientation>,
     auto_minimize_timeout: Option<i32>,
     max_activities: Option<u16>,
     max_active: Option<u16>,
     reorder_on_add: Option<bool>,
     reorder_on_reload: Option<bool>,
+    order_strategy: Option<OrderStrategy>,
+    promotion_decay: Option<u64>,
+    update_promotion_interval: Option<i32>,
+    expanded_popup: Option<bool>,
+    swipe_sensitivity: Option<f64>,
+    max_notifications: Option<u16>,
+    grid_columns: Option<u16>,
+    drag_reorder: Option<bool>,
+    idle_collapse_timeout: Option<i32>,
+    fullscreen: Option<FullscreenMode>,
     activity_order: Option<Vec<String>>,
 }
 
 impl DeDynamicLayoutConfig {
-    pub(super) fn get_order(order: Option<Vec<String>>) -> Vec<ActivityMatch> {
-        let mut matches = Vec::new();
-        if let Some(order) = order {
-            for rule in order.iter() {
-                if let Ok(rule) = ActivityMatch::from_str(rule) {
-                    matches.push(rule);
-                }
-            }
-        }
-        matches
+    /// Replaces the values that are set in `other`
+    fn overlay(&mut self, other: &DeDynamicLayoutConfig) {
+        let other = other.clone();
+        // merged field by field in `into_main_config`
+        self.window_position_overlays.extend(
+            other
+                .window_position
+                .into_iter()
+                .chain(other.window_position_overlays),
+        );
+        self.orientation = other.orientation.or(self.orientation.take());
+        self.auto_minimize_timeout = other
+            .auto_minimize_timeout
+            .or(self.auto_minimize_timeout.take());
+        self.max_activities = other.max_activities.or(self.max_activities.take());
+        self.max_active = other.max_active.or(self.max_active.take());
+        self.reorder_on_add = other.reorder_on_add.or(self.reorder_on_add.take());
+        self.reorder_on_reload = other.reorder_on_reload.or(self.reorder_on_reload.take());
+        self.order_strategy = other.order_strategy.or(self.order_strategy.take());
+        self.promotion_decay = other.promotion_decay.or(self.promotion_decay.take());
+        self.update_promotion_interval = other
+            .update_promotion_interval
+            .or(self.update_promotion_interval.take());
+        self.swipe_sensitivity = other.swipe_sensitivity.or(self.swipe_sensitivity.take());
+        self.max_notifications = other.max_notifications.or(self.max_notifications.take());
+        self.expanded_popup = other.expanded_popup.or(self.expanded_popup.take());
+        self.grid_columns = other.grid_columns.or(self.grid_columns.take());
+        self.drag_reorder = other.drag_reorder.or(self.drag_reorder.take());
+        self.idle_collapse_timeout = other
+            .idle_collapse_timeout
+            .or(self.idle_collapse_timeout.take());
+        self.fullscreen = other.fullscreen.or(self.fullscreen.take());
+        self.activity_order = other.activity_order.or(self.activity_order.take());
     }
-}
 
-#[derive(Debug, Clone)]
-pub enum ActivityMatch {
-    Activity(ActivityIdentifier),
-    Module(String),
-    Other,
-}
-impl Display for ActivityMatch {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        match self {
-            ActivityMatch::Activity(id) => write!(f, "{}@{}", id.activity(), id.module()),
-            ActivityMatch::Module(module) => write!(f, "{}", module),
-            ActivityMatch::Other => write!(f, "*"),
-        }
-    }
-}
-impl Serialize for ActivityMatch {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
-        let disp = self.to_string();
-        serializer.serialize_str(&disp)
-    }
-}
-impl FromStr for ActivityMatch {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let split = s.split('@').collect::<Vec<&str>>();
-        match split.len() {
-            1 => Ok(ActivityMatch::Module(split[0].to_string())),
-            2 => Ok(ActivityMatch::Activity(ActivityIdentifier::new(
-                split[1], split[0],
-            ))),
-            _ => Err(String::from("invalid match")),
-        }
-    }
-}
-impl ActivityMatch {
-    pub(crate) fn contains(&self, id: &ActivityIdentifier) -> bool {
-        match self {
-            ActivityMatch::Activity(activity_id) => {
-                id.module().eq_ignore_ascii_case(&activity_id.module())
-                    && id.activity().eq_ignore_ascii_case(&activity_id.activity())
-            }
-            ActivityMatch::Module(module) => id.module().eq_ignore_ascii_case(module),
-            ActivityMatch::Other => true,
-        }
+    /// Invalid rules are skipped, they are reported by [`DeDynamicLayoutConfigMain::validate`]
+    pub(super) fn get_order(order: Option<Vec<String>>) -> Vec<ActivityMatch> {
+        order
+            .into_iter()
+            .flatten()
+            .filter_map(|rule| ActivityMatch::from_str(&rule).ok())
+            .collect()
     }
 }
 
@@ -248,21 +576,20 @@ impl DynamicLayout {
     pub(crate) fn configure_widget(&self, activity_id: &ActivityIdentifier) {
         let (widget, window_name) =
             Self::find_widget(&self.order_managers.borrow(), activity_id).unwrap();
+        let config = self.config_for_window(&window_name);
 
-        widget.set_valign(
-            self.config
-                .get_for_window(&window_name)
-                .window_position
-                .v_anchor
-                .map_gtk(),
-        );
-        widget.set_halign(
-            self.config
-                .get_for_window(&window_name)
-                .window_position
-                .h_anchor
-                .map_gtk(),
-        );
+        match config.orientation {
+            Orientation::Horizontal => {
+                widget.set_valign(config.window_position.v_anchor.map_gtk());
+                widget.set_halign(config.window_position.h_anchor.map_gtk());
+            }
+            Orientation::Vertical => {
+                // the cross axis follows the anchor, the main axis is handled by the container
+                widget.set_halign(config.window_position.h_anchor.map_gtk());
+                widget.set_valign(gtk::Align::Center);
+            }
+        }
+        widget.set_focusable(config.window_position.keyboard_mode != KeyboardMode::None);
 
         // remove old controllers
         let mut controllers_removed = 0;
@@ -274,7 +601,11 @@ impl DynamicLayout {
             .flat_map(|c| c.downcast::<EventController>())
         {
             if let Some(name) = controller.name() {
-                if name == "press_gesture" || name == "focus_controller" {
+                if name == "press_gesture"
+                    || name == "long_press_gesture"
+                    || name == "focus_controller"
+                    || name == "drag_source"
+                {
                     controllers.push(controller);
                     controllers_removed += 1;
                 }
@@ -285,29 +616,50 @@ impl DynamicLayout {
         }
         // connect deactivate if it's not already connected
         if controllers_removed == 0 {
-            let send_deactivate = self.deactivate_widget.0.clone();
+            let send_deactivate = self.deactivate_widget.clone();
+            let send_promote = self.promote_widget.clone();
+            let send_idle_reset = self.idle_reset.clone();
+            let send_popup_update = self.popup_update.clone();
             let id = activity_id.clone();
             widget.connect_mode_notify(move |aw| {
-                if aw.last_mode() == ActivityMode::Minimal || aw.mode() != ActivityMode::Minimal {
-                    return;
-                }
                 if aw.has_css_class("being-added") {
                     return;
                 }
-                if let Err(err) = send_deactivate.send(id.clone()) {
-                    log::error!("error activating widget: {err}");
+                if let Err(err) = send_popup_update.send(id.clone()) {
+                    log::error!("error updating popup: {err}");
+                }
+                if matches!(aw.mode(), ActivityMode::Expanded | ActivityMode::Overlay) {
+                    if let Err(err) = send_idle_reset.send(id.clone()) {
+                        log::error!("error resetting idle timer: {err}");
+                    }
+                }
+                match (aw.last_mode(), aw.mode()) {
+                    (ActivityMode::Minimal, _) | (_, ActivityMode::Compact) => {}
+                    (_, ActivityMode::Minimal) => {
+                        if let Err(err) = send_deactivate.send(id.clone()) {
+                            log::error!("error deactivating widget: {err}");
+                        }
+                    }
+                    (ActivityMode::Compact, ActivityMode::Expanded | ActivityMode::Overlay) => {
+                        if let Err(err) = send_promote.send(id.clone()) {
+                            log::error!("error promoting widget: {err}");
+                        }
+                    }
+                    _ => {}
                 }
             });
+            watch_content(widget.upcast_ref(), activity_id, &self.property_update);
         }
 
         let press_gesture = gtk::GestureClick::new();
         press_gesture.set_name(Some("press_gesture"));
-        let send_activate = self.activate_widget.0.clone();
-        let send_cycle = self.cycle_channel.0.clone();
+        let send_activate = self.activate_widget.clone();
+        let send_cycle = self.cycle_channel.clone();
         // Minimal mode to Compact mode controller
         press_gesture.set_button(gdk::BUTTON_PRIMARY);
         let id = activity_id.clone();
         let window_name1 = window_name.clone();
+        let orientation = config.orientation;
         press_gesture.connect_released(move |gest, _, x, y| {
             let aw = gest.widget().downcast::<ActivityWidget>().unwrap();
             if x < 0.0
@@ -317,7 +669,11 @@ impl DynamicLayout {
             {
                 // cycle by dragging
                 if aw.mode() == ActivityMode::Minimal {
-                    if let Err(err) = send_cycle.send((window_name1.clone(), x > 0.0)) {
+                    let next = match orientation {
+                        Orientation::Horizontal => x > 0.0,
+                        Orientation::Vertical => y > 0.0,
+                    };
+                    if let Err(err) = send_cycle.send((window_name1.clone(), next)) {
                         log::error!("error activating widget: {err}");
                     }
                 }
@@ -333,69 +689,58 @@ impl DynamicLayout {
             }
         });
         widget.add_controller(press_gesture);
+        self.add_long_press_gesture(activity_id, &widget);
+
+        if config.drag_reorder {
+            self.add_drag_source(activity_id, &widget);
+        }
 
         // auto minimize (to Compact mode) controller
         let focus_controller = gtk::EventControllerMotion::new();
         focus_controller.set_name(Some("focus_controller"));
-        if self
-            .config
-            .get_for_window(&window_name)
-            .auto_minimize_timeout
-            >= 0
-        {
+        if config.auto_minimize_timeout >= 0 {
             let cancel_minimize = self.cancel_minimize.clone();
-            let timeout = self
-                .config
-                .get_for_window(&window_name)
-                .auto_minimize_timeout;
+            let timeout = config.auto_minimize_timeout;
             let activity_id = activity_id.clone();
             focus_controller.connect_leave(move |evt| {
                 let aw = evt.widget().downcast::<ActivityWidget>().unwrap();
-                let mode = aw.mode();
-                if matches!(mode, ActivityMode::Minimal | ActivityMode::Compact) {
-                    return;
-                }
-                let id = glib::timeout_add_local_once(
-                    Duration::from_millis(timeout.try_into().unwrap()),
-                    move || {
-                        if !aw.state_flags().contains(StateFlags::PRELIGHT) && aw.mode() == mode {
-                            //mouse is not on widget and mode hasn't changed
-                            aw.set_mode(ActivityMode::Compact);
-                        }
-                    },
-                );
-                let mut cancel_minimize = cancel_minimize.borrow_mut();
-                if let Some(source) = cancel_minimize.remove(&activity_id) {
-                    if glib::MainContext::default()
-                        .find_source_by_id(&source)
-                        .is_some()
-                    {
-                        source.remove();
-                    }
-                }
-
-                cancel_minimize.insert(activity_id.clone(), id);
+                schedule_minimize(&cancel_minimize, &activity_id, aw, timeout);
             });
             widget.add_controller(focus_controller);
         }
     }
 
     pub(crate) fn configure_container(&self, window_name: &str) {
-        let container = self
+        let ord = self
             .order_managers
             .borrow()
             .get(window_name)
             .unwrap()
-            .borrow()
-            .get_container()
             .clone();
-        container.set_spacing(0);
-        // if self.config.orientation_horizontal {
-        container.set_orientation(gtk::Orientation::Horizontal);
-        // } else {
-        // container.set_orientation(gtk::Orientation::Vertical);
-        // }
-        let config = self.config.get_for_window(&window_name);
+        let container = ord.borrow().get_container();
+        let config = self.config_for_window(window_name);
+        let is_grid = container
+            .layout_manager()
+            .is_some_and(|manager| manager.is::<gtk::GridLayout>());
+        if config.grid_columns > 1 {
+            if !is_grid {
+                container.set_layout_manager(Some(gtk::GridLayout::new()));
+            }
+        } else {
+            // the spacing and orientation of a gtk::Box are stored in its BoxLayout
+            if is_grid {
+                container
+                    .set_layout_manager(Some(gtk::BoxLayout::new(config.orientation.map_gtk())));
+            }
+            container.set_spacing(0);
+            container.set_orientation(config.orientation.map_gtk());
+        }
+        {
+            let mut ord = ord.borrow_mut();
+            let activity_container = ord.activity_container_mut();
+            activity_container.grid_columns = config.grid_columns;
+            activity_container.orientation = config.orientation;
+        }
         if !config.window_position.layer_shell {
             container.set_halign(config.window_position.h_anchor.map_gtk());
             container.set_valign(config.window_position.v_anchor.map_gtk());
@@ -403,6 +748,87 @@ impl DynamicLayout {
     }
 }
 
+/// Properties of a `gtk::Image` that change what it shows
+const IMAGE_PROPERTIES: [&str; 5] = ["paintable", "icon-name", "gicon", "file", "resource"];
+
+/// Sends `id` when a label, an image or a picture inside `widget` changes what it shows,
+/// that's how a property update of the module is seen from the layout.
+/// The widgets added after this call are not watched
+fn watch_content(
+    widget: &gtk::Widget,
+    id: &ActivityIdentifier,
+    send_update: &UnboundedSender<ActivityIdentifier>,
+) {
+    let mut child = widget.first_child();
+    while let Some(current) = child {
+        watch_content(&current, id, send_update);
+        child = current.next_sibling();
+    }
+    let send = {
+        let id = id.clone();
+        let send_update = send_update.clone();
+        move || {
+            if let Err(err) = send_update.send(id.clone()) {
+                log::error!("error sending property update: {err}");
+            }
+        }
+    };
+    if let Some(label) = widget.downcast_ref::<gtk::Label>() {
+        label.connect_label_notify(move |_| send());
+    } else if let Some(image) = widget.downcast_ref::<gtk::Image>() {
+        image.connect_notify_local(None, move |_, pspec| {
+            if IMAGE_PROPERTIES.contains(&pspec.name()) {
+                send();
+            }
+        });
+    } else if let Some(picture) = widget.downcast_ref::<gtk::Picture>() {
+        picture.connect_paintable_notify(move |_| send());
+    }
+}
+
+/// Name of a window in the problems of the config
+fn window_label(name: &str) -> String {
+    if name.is_empty() {
+        String::from("default window")
+    } else {
+        format!("window \"{name}\"")
+    }
+}
+
+/// Puts the activity back in compact mode after `timeout` milliseconds
+/// if the mouse isn't on it and its mode didn't change
+pub(crate) fn schedule_minimize(
+    cancel_minimize: &Rc<RefCell<HashMap<ActivityIdentifier, SourceId>>>,
+    activity_id: &ActivityIdentifier,
+    aw: ActivityWidget,
+    timeout: i32,
+) {
+    let mode = aw.mode();
+    if matches!(mode, ActivityMode::Minimal | ActivityMode::Compact) {
+        return;
+    }
+    let id = glib::timeout_add_local_once(
+        Duration::from_millis(timeout.try_into().unwrap()),
+        move || {
+            if !aw.state_flags().contains(StateFlags::PRELIGHT) && aw.mode() == mode {
+                //mouse is not on widget and mode hasn't changed
+                aw.set_mode(ActivityMode::Compact);
+            }
+        },
+    );
+    let mut cancel_minimize = cancel_minimize.borrow_mut();
+    if let Some(source) = cancel_minimize.remove(activity_id) {
+        if glib::MainContext::default()
+            .find_source_by_id(&source)
+            .is_some()
+        {
+            source.remove();
+        }
+    }
+
+    cancel_minimize.insert(activity_id.clone(), id);
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, OptDeserializeConfig)]
 pub struct WindowPosition {
     pub(crate) layer: Layer,
@@ -410,9 +836,17 @@ pub struct WindowPosition {
     pub(crate) v_anchor: Alignment,
     pub(crate) margin_x: i32,
     pub(crate) margin_y: i32,
-    pub(crate) exclusive_zone: i32,
-    pub(crate) monitor: String,
+    /// margins of a single edge, `None` uses `margin_x` or `margin_y`
+    pub(crate) margin_top: Option<i32>,
+    pub(crate) margin_bottom: Option<i32>,
+    pub(crate) margin_left: Option<i32>,
+    pub(crate) margin_right: Option<i32>,
+    pub(crate) exclusive_zone: ExclusiveZone,
+    pub(crate) monitor: MonitorConfig,
+    pub(crate) monitor_fallback: Vec<MonitorSelector>,
+    pub(crate) unmatched_monitor: UnmatchedMonitor,
     pub(crate) layer_shell: bool,
+    pub(crate) keyboard_mode: KeyboardMode,
 }
 
 impl Default for WindowPosition {
@@ -423,9 +857,16 @@ impl Default for WindowPosition {
             v_anchor: Alignment::Start,
             margin_x: 0,
             margin_y: 0,
-            exclusive_zone: -1,
-            monitor: String::from(""),
+            margin_top: None,
+            margin_bottom: None,
+            margin_left: None,
+            margin_right: None,
+            exclusive_zone: ExclusiveZone::Size(-1),
+            monitor: MonitorConfig::default(),
+            monitor_fallback: Vec::new(),
+            unmatched_monitor: UnmatchedMonitor::Compositor,
             layer_shell: true,
+            keyboard_mode: KeyboardMode::None,
         }
     }
 }
@@ -0,0 +1,165 @@
+use std::rc::Rc;
+
+use dynisland_core::{
+    abi::{gdk, glib, gtk, log, module::ActivityIdentifier},
+    graphics::activity_widget::ActivityWidget,
+};
+use gtk::prelude::*;
+
+use crate::{
+    layout::DynamicLayout, persistent_state::activity_key, priority_order::WidgetOrderManager,
+    window_position::Orientation,
+};
+
+impl DynamicLayout {
+    /// Lets the widget be dragged to another position or to another window
+    pub(crate) fn add_drag_source(
+        &self,
+        activity_id: &ActivityIdentifier,
+        widget: &ActivityWidget,
+    ) {
+        let drag_source = gtk::DragSource::new();
+        drag_source.set_name(Some("drag_source"));
+        drag_source.set_actions(gdk::DragAction::MOVE);
+        let key = activity_key(activity_id);
+        drag_source
+            .connect_prepare(move |_, _, _| Some(gdk::ContentProvider::for_value(&key.to_value())));
+        drag_source.connect_drag_begin(|source, _| {
+            if let Some(widget) = source.widget() {
+                let paintable = gtk::WidgetPaintable::new(Some(&widget));
+                source.set_icon(Some(&paintable), 0, 0);
+            }
+        });
+        widget.add_controller(drag_source);
+    }
+
+    /// Accepts the activities dropped in the container of `window_name`
+    pub(crate) fn add_drop_target(&self, container: &gtk::Box, window_name: &str) {
+        let drop_target = gtk::DropTarget::new(glib::Type::STRING, gdk::DragAction::MOVE);
+        let this = self.clone();
+        let window_name = window_name.to_string();
+        drop_target.connect_drop(move |_, value, x, y| {
+            let key = match value.get::<String>() {
+                Ok(key) => key,
+                Err(_) => return false,
+            };
+            this.drop_activity(&key, &window_name, x, y)
+        });
+        container.add_controller(drop_target);
+    }
+
+    /// Moves the activity `key` to the position `x`, `y` of the container of `window_name`
+    fn drop_activity(&self, key: &str, window_name: &str, x: f64, y: f64) -> bool {
+        let config = self.config_for_window(window_name);
+        if !config.drag_reorder {
+            return false;
+        }
+        let (id, source_ord) = match self.find_activity(key) {
+            Ok(found) => found,
+            Err(err) => {
+                log::warn!("dropped activity not found: {err}");
+                return false;
+            }
+        };
+        let target_ord = match self.order_managers.borrow().get(window_name) {
+            Some(ord) => ord.clone(),
+            None => return false,
+        };
+        let position = drop_position(&*target_ord.borrow(), &id, x, y, config.orientation);
+
+        if !Rc::ptr_eq(&source_ord, &target_ord) {
+            // same as the move command, the window is kept across restarts
+            {
+                let mut persistent_state = self.persistent_state.borrow_mut();
+                persistent_state
+                    .window_overrides
+                    .insert(activity_key(&id), window_name.to_string());
+                persistent_state.save();
+            }
+            let widget = source_ord
+                .borrow()
+                .get_widget_map()
+                .borrow()
+                .get(&id)
+                .unwrap()
+                .clone();
+            self.move_activity(&id, widget, window_name);
+        }
+        // the order manager changes if the window was recreated
+        let target_ord = match self.order_managers.borrow().get(window_name) {
+            Some(ord) => ord.clone(),
+            None => return false,
+        };
+        let current = target_ord.borrow().list_activities();
+        let mut order: Vec<&ActivityIdentifier> = current
+            .iter()
+            .map(|tid| tid.as_ref())
+            .filter(|tid| **tid != id)
+            .collect();
+        order.insert(position.min(order.len()), &id);
+        let active: Vec<Rc<ActivityIdentifier>> = current
+            .iter()
+            .filter(|tid| target_ord.borrow().is_active(tid))
+            .cloned()
+            .collect();
+        target_ord.borrow_mut().set_order_and_active(order, &active);
+
+        let mut persistent_state = self.persistent_state.borrow_mut();
+        persistent_state.save_order(window_name, &*target_ord.borrow());
+        persistent_state.save_suggestion(window_name, &*target_ord.borrow());
+        true
+    }
+}
+
+/// Returns the index, in the activities of `ord` without `dragged`,
+/// where an activity dropped at `x`, `y` should go
+fn drop_position(
+    ord: &dyn WidgetOrderManager,
+    dragged: &ActivityIdentifier,
+    x: f64,
+    y: f64,
+    orientation: Orientation,
+) -> usize {
+    let container = ord.get_container();
+    let is_grid = ord.activity_container().grid_columns > 1;
+    // the main axis is the one the activities follow, the cross axis is the one of the rows
+    let (main, cross) = match orientation {
+        Orientation::Horizontal => (x, y),
+        Orientation::Vertical => (y, x),
+    };
+    let widget_map = ord.get_widget_map();
+    let widget_map = widget_map.borrow();
+    let mut position = 0;
+    for (idx, id) in ord
+        .list_activities()
+        .iter()
+        .filter(|id| id.as_ref() != dragged)
+        .enumerate()
+    {
+        if !ord.is_shown(id) {
+            continue;
+        }
+        let bounds = match widget_map
+            .get(id)
+            .and_then(|widget| widget.compute_bounds(&container))
+        {
+            Some(bounds) => bounds,
+            None => continue,
+        };
+        let (main_start, main_size, cross_start, cross_size) = match orientation {
+            Orientation::Horizontal => (bounds.x(), bounds.width(), bounds.y(), bounds.height()),
+            Orientation::Vertical => (bounds.y(), bounds.height(), bounds.x(), bounds.width()),
+        };
+        let before = if is_grid {
+            // a previous row, or the same row and before the middle of the widget
+            ((cross_start + cross_size) as f64) <= cross
+                || ((cross_start as f64) <= cross && ((main_start + main_size / 2.0) as f64) < main)
+        } else {
+            ((main_start + main_size / 2.0) as f64) < main
+        };
+        if before {
+            position = idx + 1;
+        }
+    }
+    position
+}
@@ -0,0 +1,418 @@
+use std::{
+    collections::{HashMap, HashSet},
+    env,
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+    thread,
+    time::Duration,
+};
+
+use dynisland_core::abi::log;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::mpsc::UnboundedSender;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "FullscreenMode")]
+pub enum FullscreenMode {
+    /// the window stays on its layer
+    #[default]
+    #[serde(alias = "none")]
+    None,
+    /// the window is hidden while its monitor has a fullscreen window
+    #[serde(alias = "hide")]
+    Hide,
+    /// the window is moved to the bottom layer while its monitor has a fullscreen window
+    #[serde(alias = "bottom")]
+    Bottom,
+}
+
+/// Outputs with a fullscreen window and the windows that were hidden or lowered because of it
+#[derive(Debug, Default)]
+pub struct FullscreenState {
+    pub(crate) outputs: HashSet<String>,
+    /// window name -> connector of the monitor it was on
+    pub(crate) hidden: HashMap<String, String>,
+    /// windows moved to the bottom layer
+    pub(crate) lowered: HashSet<String>,
+}
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Compositor IPC used to find the outputs that have a fullscreen window
+#[derive(Debug, Clone)]
+pub enum IpcBackend {
+    /// sway or i3, `socket` is `$SWAYSOCK` or `$I3SOCK`
+    Sway { socket: PathBuf },
+    /// `command_socket` is `.socket.sock` and `event_socket` is `.socket2.sock`
+    Hyprland {
+        command_socket: PathBuf,
+        event_socket: PathBuf,
+    },
+}
+
+impl IpcBackend {
+    pub fn from_env() -> Option<Self> {
+        if let Some(socket) = env::var_os("SWAYSOCK").or_else(|| env::var_os("I3SOCK")) {
+            return Some(IpcBackend::Sway {
+                socket: socket.into(),
+            });
+        }
+        let signature = env::var_os("HYPRLAND_INSTANCE_SIGNATURE")?;
+        // older versions of Hyprland put the sockets in /tmp
+        let dir = env::var_os("XDG_RUNTIME_DIR")
+            .map(|dir| PathBuf::from(dir).join("hypr").join(&signature))
+            .filter(|dir| dir.exists())
+            .unwrap_or_else(|| PathBuf::from("/tmp/hypr").join(&signature));
+        Some(IpcBackend::Hyprland {
+            command_socket: dir.join(".socket.sock"),
+            event_socket: dir.join(".socket2.sock"),
+        })
+    }
+
+    /// Starts a thread that sends the fullscreen outputs every time they change,
+    /// it reconnects if the connection is lost and stops when the receiver is dropped
+    /// (it's checked before waiting for the next event)
+    pub fn spawn_watcher(self, sender: UnboundedSender<HashSet<String>>) {
+        let result = thread::Builder::new()
+            .name("fullscreen-ipc".to_string())
+            .spawn(move || loop {
+                if sender.is_closed() {
+                    return;
+                }
+                let result = match &self {
+                    IpcBackend::Sway { socket } => watch_sway(socket, &sender),
+                    IpcBackend::Hyprland {
+                        command_socket,
+                        event_socket,
+                    } => watch_hyprland(command_socket, event_socket, &sender),
+                };
+                if let Err(err) = result {
+                    log::warn!("fullscreen ipc error: {err}, reconnecting");
+                    // don't keep the windows hidden while disconnected
+                    let _ = sender.send(HashSet::new());
+                }
+                thread::sleep(RECONNECT_DELAY);
+            });
+        if let Err(err) = result {
+            log::error!("failed to start the fullscreen ipc thread: {err}");
+        }
+    }
+}
+
+/// Sends `outputs` if they changed, returns false if the receiver was dropped
+fn send_if_changed(
+    sender: &UnboundedSender<HashSet<String>>,
+    last: &mut Option<HashSet<String>>,
+    outputs: HashSet<String>,
+) -> bool {
+    if last.as_ref() == Some(&outputs) {
+        return true;
+    }
+    *last = Some(outputs.clone());
+    sender.send(outputs).is_ok()
+}
+
+fn invalid_data(err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err.to_string())
+}
+
+const I3_MAGIC: &[u8; 6] = b"i3-ipc";
+const I3_GET_WORKSPACES: u32 = 1;
+const I3_SUBSCRIBE: u32 = 2;
+const I3_GET_TREE: u32 = 4;
+
+fn sway_send(stream: &mut UnixStream, message_type: u32, payload: &[u8]) -> io::Result<()> {
+    let mut message = Vec::with_capacity(I3_MAGIC.len() + 8 + payload.len());
+    message.extend_from_slice(I3_MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&message_type.to_ne_bytes());
+    message.extend_from_slice(payload);
+    stream.write_all(&message)
+}
+
+/// Reads a reply or an event, returns its type and its payload
+fn sway_read(stream: &mut UnixStream) -> io::Result<(u32, Vec<u8>)> {
+    let mut header = [0u8; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != I3_MAGIC {
+        return Err(invalid_data("invalid i3 ipc message"));
+    }
+    let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut payload = vec![0u8; length as usize];
+    stream.read_exact(&mut payload)?;
+    Ok((message_type, payload))
+}
+
+fn sway_request(stream: &mut UnixStream, message_type: u32) -> io::Result<Value> {
+    sway_send(stream, message_type, b"")?;
+    let (_, payload) = sway_read(stream)?;
+    serde_json::from_slice(&payload).map_err(invalid_data)
+}
+
+fn sway_fullscreen_outputs(stream: &mut UnixStream) -> io::Result<HashSet<String>> {
+    let workspaces = sway_request(stream, I3_GET_WORKSPACES)?;
+    let visible: HashSet<&str> = workspaces
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|workspace| workspace["visible"].as_bool() == Some(true))
+        .filter_map(|workspace| workspace["name"].as_str())
+        .collect();
+    let tree = sway_request(stream, I3_GET_TREE)?;
+    let mut outputs = HashSet::new();
+    for output in tree["nodes"].as_array().into_iter().flatten() {
+        let name = match output["name"].as_str() {
+            Some(name) => name,
+            None => continue,
+        };
+        let fullscreen = output["nodes"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|workspace| {
+                workspace["name"]
+                    .as_str()
+                    .is_some_and(|name| visible.contains(name))
+            })
+            .any(sway_has_fullscreen_child);
+        if fullscreen {
+            outputs.insert(name.to_string());
+        }
+    }
+    Ok(outputs)
+}
+
+/// Workspaces are always fullscreen on their output, so only the children are checked
+fn sway_has_fullscreen_child(node: &Value) -> bool {
+    ["nodes", "floating_nodes"].iter().any(|key| {
+        node[*key].as_array().into_iter().flatten().any(|child| {
+            child["fullscreen_mode"].as_u64().unwrap_or(0) > 0 || sway_has_fullscreen_child(child)
+        })
+    })
+}
+
+fn watch_sway(socket: &Path, sender: &UnboundedSender<HashSet<String>>) -> io::Result<()> {
+    let mut events = UnixStream::connect(socket)?;
+    sway_send(
+        &mut events,
+        I3_SUBSCRIBE,
+        br#"["window","workspace","output"]"#,
+    )?;
+    let (_, reply) = sway_read(&mut events)?;
+    let reply: Value = serde_json::from_slice(&reply).map_err(invalid_data)?;
+    if reply["success"].as_bool() != Some(true) {
+        return Err(invalid_data("failed to subscribe to the i3 ipc events"));
+    }
+    let mut requests = UnixStream::connect(socket)?;
+    let mut last = None;
+    loop {
+        let outputs = sway_fullscreen_outputs(&mut requests)?;
+        if !send_if_changed(sender, &mut last, outputs) || sender.is_closed() {
+            return Ok(());
+        }
+        sway_read(&mut events)?;
+    }
+}
+
+/// Events of socket2 that can change which outputs have a fullscreen window
+const HYPRLAND_EVENTS: &[&str] = &[
+    "fullscreen",
+    "workspace",
+    "workspacev2",
+    "focusedmon",
+    "openwindow",
+    "closewindow",
+    "movewindow",
+    "movewindowv2",
+    "moveworkspace",
+    "moveworkspacev2",
+    "monitoradded",
+    "monitoraddedv2",
+    "monitorremoved",
+];
+
+fn hyprland_request(socket: &Path, request: &str) -> io::Result<Value> {
+    let mut stream = UnixStream::connect(socket)?;
+    stream.write_all(request.as_bytes())?;
+    let mut response = Vec::new();
+    stream.read_to_end(&mut response)?;
+    serde_json::from_slice(&response).map_err(invalid_data)
+}
+
+fn hyprland_fullscreen_outputs(socket: &Path) -> io::Result<HashSet<String>> {
+    let workspaces = hyprland_request(socket, "j/workspaces")?;
+    let fullscreen_workspaces: HashSet<i64> = workspaces
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|workspace| workspace["hasfullscreen"].as_bool() == Some(true))
+        .filter_map(|workspace| workspace["id"].as_i64())
+        .collect();
+    let monitors = hyprland_request(socket, "j/monitors")?;
+    let outputs = monitors
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|monitor| {
+            monitor["activeWorkspace"]["id"]
+                .as_i64()
+                .is_some_and(|id| fullscreen_workspaces.contains(&id))
+        })
+        .filter_map(|monitor| monitor["name"].as_str())
+        .map(|name| name.to_string())
+        .collect();
+    Ok(outputs)
+}
+
+fn watch_hyprland(
+    command_socket: &Path,
+    event_socket: &Path,
+    sender: &UnboundedSender<HashSet<String>>,
+) -> io::Result<()> {
+    let mut events = BufReader::new(UnixStream::connect(event_socket)?);
+    let mut last = None;
+    let mut line = String::new();
+    loop {
+        let outputs = hyprland_fullscreen_outputs(command_socket)?;
+        if !send_if_changed(sender, &mut last, outputs) {
+            return Ok(());
+        }
+        // wait for an event that can change the outputs
+        loop {
+            if sender.is_closed() {
+                return Ok(());
+            }
+            if hyprland_read_event(&mut events, &mut line)? {
+                break;
+            }
+        }
+    }
+}
+
+/// Reads an event line of socket2, returns true if it can change the fullscreen outputs
+fn hyprland_read_event(events: &mut impl BufRead, line: &mut String) -> io::Result<bool> {
+    line.clear();
+    if events.read_line(line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "hyprland closed the event socket",
+        ));
+    }
+    let event = line.split(">>").next().unwrap_or_default();
+    Ok(HYPRLAND_EVENTS.contains(&event))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{io::Cursor, os::unix::net::UnixListener};
+
+    use serde_json::json;
+
+    use super::*;
+
+    fn sway_reply(stream: &mut UnixStream, expected_type: u32, reply: Value) {
+        let (message_type, _) = sway_read(stream).unwrap();
+        assert_eq!(message_type, expected_type);
+        sway_send(stream, message_type, reply.to_string().as_bytes()).unwrap();
+    }
+
+    #[test]
+    fn sway_message_round_trip() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        sway_send(&mut client, I3_SUBSCRIBE, br#"["window"]"#).unwrap();
+        let (message_type, payload) = sway_read(&mut server).unwrap();
+        assert_eq!(message_type, I3_SUBSCRIBE);
+        assert_eq!(payload, br#"["window"]"#);
+    }
+
+    #[test]
+    fn sway_invalid_magic() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        client.write_all(b"i4-ipc\0\0\0\0\0\0\0\0").unwrap();
+        let err = sway_read(&mut server).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn sway_outputs() {
+        let (mut client, mut server) = UnixStream::pair().unwrap();
+        let compositor = thread::spawn(move || {
+            sway_reply(
+                &mut server,
+                I3_GET_WORKSPACES,
+                json!([
+                    {"name": "1", "visible": true},
+                    {"name": "2", "visible": false},
+                    {"name": "3", "visible": true},
+                ]),
+            );
+            let fullscreen_window = json!({"fullscreen_mode": 1, "nodes": []});
+            let window = json!({"fullscreen_mode": 0, "nodes": []});
+            sway_reply(
+                &mut server,
+                I3_GET_TREE,
+                json!({"nodes": [
+                    {"name": "DP-1", "nodes": [
+                        // a fullscreen window in a nested container
+                        {"name": "1", "nodes": [{"nodes": [fullscreen_window]}]},
+                    ]},
+                    {"name": "DP-2", "nodes": [
+                        // not visible
+                        {"name": "2", "floating_nodes": [fullscreen_window]},
+                        {"name": "3", "nodes": [window]},
+                    ]},
+                ]}),
+            );
+        });
+        let outputs = sway_fullscreen_outputs(&mut client).unwrap();
+        compositor.join().unwrap();
+        assert_eq!(outputs, HashSet::from(["DP-1".to_string()]));
+    }
+
+    #[test]
+    fn hyprland_outputs() {
+        let dir = env::temp_dir().join(format!("dynisland-hyprland-test-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let socket = dir.join(".socket.sock");
+        let _ = std::fs::remove_file(&socket);
+        let listener = UnixListener::bind(&socket).unwrap();
+        let compositor = thread::spawn(move || {
+            for _ in 0..2 {
+                let (mut stream, _) = listener.accept().unwrap();
+                let mut request = [0u8; 64];
+                let length = stream.read(&mut request).unwrap();
+                let reply = match &request[..length] {
+                    b"j/workspaces" => json!([
+                        {"id": 1, "hasfullscreen": true},
+                        {"id": 2, "hasfullscreen": false},
+                        {"id": 3, "hasfullscreen": true},
+                    ]),
+                    b"j/monitors" => json!([
+                        {"name": "DP-1", "activeWorkspace": {"id": 1}},
+                        {"name": "DP-2", "activeWorkspace": {"id": 2}},
+                    ]),
+                    request => panic!("unexpected request {request:?}"),
+                };
+                stream.write_all(reply.to_string().as_bytes()).unwrap();
+            }
+        });
+        let outputs = hyprland_fullscreen_outputs(&socket).unwrap();
+        compositor.join().unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(outputs, HashSet::from(["DP-1".to_string()]));
+    }
+
+    #[test]
+    fn hyprland_events() {
+        let mut events = Cursor::new("activewindow>>kitty,~\nfullscreen>>1\nworkspacev2>>2,2\n");
+        let mut line = String::new();
+        assert!(!hyprland_read_event(&mut events, &mut line).unwrap());
+        assert!(hyprland_read_event(&mut events, &mut line).unwrap());
+        assert!(hyprland_read_event(&mut events, &mut line).unwrap());
+        let err = hyprland_read_event(&mut events, &mut line).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::UnexpectedEof);
+    }
+}
@@ -0,0 +1,109 @@
+use std::{cell::Cell, rc::Rc};
+
+use dynisland_core::{
+    abi::{glib, gtk, log, module::ActivityIdentifier},
+    graphics::activity_widget::ActivityWidget,
+};
+use gtk::prelude::*;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::{keyboard::expand_activity, layout::DynamicLayout, window_position::Orientation};
+
+/// Velocity in pixels per second a swipe needs to cycle with a sensitivity of 1
+const SWIPE_VELOCITY: f64 = 300.0;
+/// Distance in pixels a touchpad scroll needs to cycle with a sensitivity of 1,
+/// a mouse wheel cycles once every notch
+const SCROLL_DISTANCE: f64 = 50.0;
+
+impl DynamicLayout {
+    /// Cycles the activities of the window with touch swipes and with scrolling
+    pub(crate) fn add_cycle_gestures(&self, container: &gtk::Box, window_name: &str) {
+        let swipe_gesture = gtk::GestureSwipe::new();
+        // dragging with the mouse is already used to cycle and to reorder
+        swipe_gesture.set_touch_only(true);
+        let this = self.clone();
+        let send_cycle = self.cycle_channel.clone();
+        let window_name1 = window_name.to_string();
+        swipe_gesture.connect_swipe(move |_, velocity_x, velocity_y| {
+            let config = this.config_for_window(&window_name1);
+            if config.swipe_sensitivity <= 0.0 {
+                return;
+            }
+            let velocity = match config.orientation {
+                Orientation::Horizontal => velocity_x,
+                Orientation::Vertical => velocity_y,
+            };
+            if velocity.abs() * config.swipe_sensitivity < SWIPE_VELOCITY {
+                return;
+            }
+            // swiping towards the start brings in the next activities
+            send_cycle_to(&send_cycle, &window_name1, velocity < 0.0);
+        });
+        container.add_controller(swipe_gesture);
+
+        let scroll_controller =
+            gtk::EventControllerScroll::new(gtk::EventControllerScrollFlags::BOTH_AXES);
+        // only touchpads start and end a scroll
+        let touchpad = Rc::new(Cell::new(false));
+        let scrolled = Rc::new(Cell::new(0.0));
+        let touchpad1 = touchpad.clone();
+        scroll_controller.connect_scroll_begin(move |_| {
+            touchpad1.set(true);
+        });
+        let touchpad1 = touchpad.clone();
+        let scrolled1 = scrolled.clone();
+        scroll_controller.connect_scroll_end(move |_| {
+            touchpad1.set(false);
+            scrolled1.set(0.0);
+        });
+        let this = self.clone();
+        let send_cycle = self.cycle_channel.clone();
+        let window_name1 = window_name.to_string();
+        scroll_controller.connect_scroll(move |_, dx, dy| {
+            let config = this.config_for_window(&window_name1);
+            if config.swipe_sensitivity <= 0.0 {
+                return glib::Propagation::Proceed;
+            }
+            let distance =
+                if touchpad.get() { SCROLL_DISTANCE } else { 1.0 } / config.swipe_sensitivity;
+            scrolled.set(scrolled.get() + dx + dy);
+            while scrolled.get().abs() >= distance {
+                let next = scrolled.get() > 0.0;
+                scrolled.set(scrolled.get() - distance.copysign(scrolled.get()));
+                send_cycle_to(&send_cycle, &window_name1, next);
+            }
+            glib::Propagation::Stop
+        });
+        container.add_controller(scroll_controller);
+    }
+
+    /// Long pressing the widget activates it and puts it in expanded mode
+    pub(crate) fn add_long_press_gesture(
+        &self,
+        activity_id: &ActivityIdentifier,
+        widget: &ActivityWidget,
+    ) {
+        let long_press_gesture = gtk::GestureLongPress::new();
+        long_press_gesture.set_name(Some("long_press_gesture"));
+        let order_managers = self.order_managers.clone();
+        let persistent_state = self.persistent_state.clone();
+        let id = activity_id.clone();
+        long_press_gesture.connect_pressed(move |gest, _, _| {
+            let (widget, window_name) = match Self::find_widget(&order_managers.borrow(), &id) {
+                Some(found) => found,
+                None => return,
+            };
+            let ord = order_managers.borrow().get(&window_name).unwrap().clone();
+            // the click that ends the long press doesn't activate the widget again
+            gest.set_state(gtk::EventSequenceState::Claimed);
+            expand_activity(&ord, &id, &widget, &window_name, &persistent_state);
+        });
+        widget.add_controller(long_press_gesture);
+    }
+}
+
+fn send_cycle_to(send_cycle: &UnboundedSender<(String, bool)>, window_name: &str, next: bool) {
+    if let Err(err) = send_cycle.send((window_name.to_string(), next)) {
+        log::error!("error cycling activities: {err}");
+    }
+}
@@ -0,0 +1,151 @@
+use std::{cell::RefCell, rc::Rc};
+
+use dynisland_core::{
+    abi::{gdk, glib, gtk, module::ActivityIdentifier},
+    graphics::activity_widget::{boxed_activity_mode::ActivityMode, ActivityWidget},
+};
+use gtk::prelude::*;
+
+use crate::{
+    layout::DynamicLayout,
+    persistent_state::PersistentState,
+    priority_order::{activate_and_refresh, WidgetOrderManager},
+    window_position::Orientation,
+};
+
+impl DynamicLayout {
+    /// The arrow keys move the focus between the shown activities of the window,
+    /// Enter expands the focused activity and Escape puts it back in compact mode
+    pub(crate) fn add_keyboard_controller(&self, window: &gtk::Window, window_name: &str) {
+        let key_controller = gtk::EventControllerKey::new();
+        let order_managers = self.order_managers.clone();
+        let persistent_state = self.persistent_state.clone();
+        let window_name = window_name.to_string();
+        key_controller.connect_key_pressed(move |_, key, _, _| {
+            let ord = match order_managers.borrow().get(&window_name) {
+                Some(ord) => ord.clone(),
+                None => return glib::Propagation::Proceed,
+            };
+            let (row_step, line_step) = line_steps(&*ord.borrow());
+            let handled = match key {
+                gdk::Key::Left => move_focus(&*ord.borrow(), -row_step),
+                gdk::Key::Right => move_focus(&*ord.borrow(), row_step),
+                gdk::Key::Up => move_focus(&*ord.borrow(), -line_step),
+                gdk::Key::Down => move_focus(&*ord.borrow(), line_step),
+                gdk::Key::Return | gdk::Key::KP_Enter => {
+                    expand_focused(&ord, &window_name, &persistent_state)
+                }
+                gdk::Key::Escape => collapse_focused(&*ord.borrow()),
+                _ => false,
+            };
+            if handled {
+                glib::Propagation::Stop
+            } else {
+                glib::Propagation::Proceed
+            }
+        });
+        window.add_controller(key_controller);
+    }
+}
+
+/// Returns how many positions the Left/Right and the Up/Down arrows move the focus,
+/// in a grid the arrows that go across the rows skip a whole row
+fn line_steps(ord: &dyn WidgetOrderManager) -> (isize, isize) {
+    let container = ord.activity_container();
+    if container.grid_columns <= 1 {
+        return (1, 1);
+    }
+    let columns = container.grid_columns as isize;
+    match container.orientation {
+        Orientation::Horizontal => (1, columns),
+        Orientation::Vertical => (columns, 1),
+    }
+}
+
+/// Returns the activity that has the focus in the window
+fn focused_activity(
+    ord: &dyn WidgetOrderManager,
+) -> Option<(Rc<ActivityIdentifier>, ActivityWidget)> {
+    let focus_child = ord.get_container().focus_child()?;
+    let widget_map = ord.get_widget_map();
+    let widget_map = widget_map.borrow();
+    widget_map
+        .iter()
+        .find(|(_, widget)| **widget == focus_child)
+        .map(|(id, widget)| (id.clone(), widget.clone()))
+}
+
+/// Focuses the shown activity `step` positions after the focused one,
+/// or the first (last if `step` is negative) if none is focused
+fn move_focus(ord: &dyn WidgetOrderManager, step: isize) -> bool {
+    let shown: Vec<Rc<ActivityIdentifier>> = ord
+        .list_activities()
+        .into_iter()
+        .filter(|id| ord.is_shown(id))
+        .collect();
+    if shown.is_empty() {
+        return false;
+    }
+    let current = focused_activity(ord).and_then(|(id, _)| shown.iter().position(|t| *t == id));
+    let next = match current {
+        Some(pos) => (pos as isize + step).clamp(0, shown.len() as isize - 1) as usize,
+        None if step > 0 => 0,
+        None => shown.len() - 1,
+    };
+    let widget = ord.get_widget_map().borrow().get(&shown[next]).cloned();
+    match widget {
+        Some(widget) => widget.grab_focus(),
+        None => false,
+    }
+}
+
+/// Activates the focused activity if needed and puts it in expanded mode
+fn expand_focused(
+    ord: &Rc<RefCell<dyn WidgetOrderManager>>,
+    window_name: &str,
+    persistent_state: &Rc<RefCell<PersistentState>>,
+) -> bool {
+    let (id, widget) = match focused_activity(&*ord.borrow()) {
+        Some(focused) => focused,
+        None => return false,
+    };
+    expand_activity(ord, &id, &widget, window_name, persistent_state);
+    true
+}
+
+/// Activates the activity if needed and puts it in expanded mode
+pub(crate) fn expand_activity(
+    ord: &Rc<RefCell<dyn WidgetOrderManager>>,
+    id: &ActivityIdentifier,
+    widget: &ActivityWidget,
+    window_name: &str,
+    persistent_state: &Rc<RefCell<PersistentState>>,
+) {
+    if !ord.borrow().is_active(id) {
+        activate_and_refresh(ord, id);
+        persistent_state
+            .borrow_mut()
+            .save_order(window_name, &*ord.borrow());
+    }
+    widget.set_mode(ActivityMode::Expanded);
+}
+
+/// Puts the focused activity back in compact mode, or minimal mode if it's not active
+fn collapse_focused(ord: &dyn WidgetOrderManager) -> bool {
+    let (id, widget) = match focused_activity(ord) {
+        Some(focused) => focused,
+        None => return false,
+    };
+    if !matches!(
+        widget.mode(),
+        ActivityMode::Expanded | ActivityMode::Overlay
+    ) {
+        return false;
+    }
+    if ord.is_active(&id) {
+        widget.set_mode(ActivityMode::Compact);
+    } else {
+        widget.set_mode(ActivityMode::Minimal);
+    }
+    true
+}
@@ -1,4 +1,10 @@
-use std::{cell::RefCell, collections::HashMap, rc::Rc, time::Duration};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashMap, HashSet},
+    ops::Deref,
+    rc::Rc,
+    time::{Duration, Instant},
+};
 
 use abi_stable::{
     sabi_extern_fn,
@@ -12,7 +18,7 @@ use abi_stable::{
 use anyhow::Result;
 use dynisland_core::{
     abi::{
-        abi_stable, gdk, glib, gtk,
+        abi_stable, gdk, glib, gtk, gtk_layer_shell,
         layout::{LayoutManagerType, SabiLayoutManager, SabiLayoutManager_TO},
         log,
         module::ActivityIdentifier,
@@ -24,37 +30,90 @@ use dynisland_core::{
 #[cfg(not(feature = "embedded"))]
 use env_logger::Env;
 use glib::SourceId;
-use gtk::prelude::*;
+use gtk::{prelude::*, StateFlags};
+use gtk_layer_shell::LayerShell;
 #[cfg(not(feature = "embedded"))]
 use log::Level;
 use ron::ser::PrettyConfig;
 use tokio::sync::mpsc::{UnboundedReceiver, UnboundedSender};
 
 use crate::{
+    activity_match::ActivityMatch,
     config::{
-        self, ActivityMatch, DeDynamicLayoutConfigMain, DynamicLayoutConfig,
-        DynamicLayoutConfigMain,
+        self, DeDynamicLayoutConfigMain, DynamicLayoutConfig, DynamicLayoutConfigMain,
+        WindowInstance,
+    },
+    fullscreen::{FullscreenMode, FullscreenState, IpcBackend},
+    layout_state::LayoutState,
+    notification_queue::{Notification, NotificationQueue},
+    persistent_state::{activity_key, PersistentState},
+    popup::close_window_popup,
+    priority_order::{
+        activate_and_refresh, new_order_manager, schedule_refresh, set_collapsed, OrderStrategy,
+        WidgetOrderManager,
     },
-    priority_order::cycle_order::CycleOrder,
+    profile::{ProfileChoice, ProfileState},
+    window_position::{window_connector, ExclusiveZone},
 };
 
+/// Handle to the layout, the clones share the same state and can be moved in closures
+#[derive(Clone)]
 pub struct DynamicLayout {
+    inner: Rc<DynamicLayoutInner>,
+}
+
+impl Deref for DynamicLayout {
+    type Target = DynamicLayoutInner;
+
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
+}
+
+pub struct DynamicLayoutInner {
     pub(crate) app: gtk::Application,
     pub(crate) cancel_minimize: Rc<RefCell<HashMap<ActivityIdentifier, SourceId>>>,
-    pub(crate) order_managers: Rc<RefCell<HashMap<String, Rc<RefCell<CycleOrder>>>>>,
-    pub(crate) activate_widget: (
-        UnboundedSender<ActivityIdentifier>,
-        Option<UnboundedReceiver<ActivityIdentifier>>,
-    ),
-    pub(crate) deactivate_widget: (
-        UnboundedSender<ActivityIdentifier>,
-        Option<UnboundedReceiver<ActivityIdentifier>>,
-    ),
-    pub(crate) cycle_channel: (
-        UnboundedSender<(String, bool)>,
-        Option<UnboundedReceiver<(String, bool)>>,
-    ),
-    pub(crate) config: DynamicLayoutConfigMain,
+    pub(crate) order_managers: Rc<RefCell<HashMap<String, Rc<RefCell<dyn WidgetOrderManager>>>>>,
+    /// window name -> instance it was created for
+    pub(crate) window_instances: RefCell<HashMap<String, WindowInstance>>,
+    pub(crate) activate_widget: UnboundedSender<ActivityIdentifier>,
+    pub(crate) deactivate_widget: UnboundedSender<ActivityIdentifier>,
+    pub(crate) cycle_channel: UnboundedSender<(String, bool)>,
+    pub(crate) promote_widget: UnboundedSender<ActivityIdentifier>,
+    /// activities whose content changed after a property update
+    pub(crate) property_update: UnboundedSender<ActivityIdentifier>,
+    pub(crate) idle_reset: UnboundedSender<ActivityIdentifier>,
+    /// activities whose mode changed, they are moved in or out of the popup
+    pub(crate) popup_update: UnboundedSender<ActivityIdentifier>,
+    /// window name -> timer that collapses it
+    pub(crate) idle_timers: Rc<RefCell<HashMap<String, SourceId>>>,
+    pub(crate) notification_queues: Rc<RefCell<HashMap<String, NotificationQueue>>>,
+    pub(crate) fullscreen_channel: UnboundedSender<HashSet<String>>,
+    pub(crate) fullscreen_state: Rc<RefCell<FullscreenState>>,
+    pub(crate) fullscreen_watcher_started: Cell<bool>,
+    pub(crate) config: Rc<RefCell<DynamicLayoutConfigMain>>,
+    /// last config sent by dynisland, used by the validate command
+    pub(crate) config_source: RefCell<String>,
+    /// last config sent by dynisland, before applying a profile
+    pub(crate) base_config: RefCell<DeDynamicLayoutConfigMain>,
+    pub(crate) profile_state: RefCell<ProfileState>,
+    /// window name -> css classes added with `add-css` to a window that was closed,
+    /// they are added again when the window is created
+    pub(crate) closed_css_classes: RefCell<HashMap<String, Vec<String>>>,
+    pub(crate) persistent_state: Rc<RefCell<PersistentState>>,
+    /// taken by the event listener when the layout is initialized
+    receivers: RefCell<Option<EventReceivers>>,
+}
+
+struct EventReceivers {
+    activate_widget: UnboundedReceiver<ActivityIdentifier>,
+    deactivate_widget: UnboundedReceiver<ActivityIdentifier>,
+    cycle_channel: UnboundedReceiver<(String, bool)>,
+    promote_widget: UnboundedReceiver<ActivityIdentifier>,
+    property_update: UnboundedReceiver<ActivityIdentifier>,
+    idle_reset: UnboundedReceiver<ActivityIdentifier>,
+    popup_update: UnboundedReceiver<ActivityIdentifier>,
+    fullscreen_channel: UnboundedReceiver<HashSet<String>>,
 }
 
 #[sabi_extern_fn]
@@ -63,77 +122,107 @@ pub fn new(app: SabiApplication) -> RResult<LayoutManagerType, RBoxError> {
     env_logger::Builder::from_env(Env::default().default_filter_or(Level::Warn.as_str())).init();
 
     let app = app.try_into().unwrap();
-    let channel = tokio::sync::mpsc::unbounded_channel();
-    let channel1 = tokio::sync::mpsc::unbounded_channel();
-    let channel2 = tokio::sync::mpsc::unbounded_channel();
+    let activate_widget = tokio::sync::mpsc::unbounded_channel();
+    let deactivate_widget = tokio::sync::mpsc::unbounded_channel();
+    let cycle_channel = tokio::sync::mpsc::unbounded_channel();
+    let promote_widget = tokio::sync::mpsc::unbounded_channel();
+    let property_update = tokio::sync::mpsc::unbounded_channel();
+    let idle_reset = tokio::sync::mpsc::unbounded_channel();
+    let popup_update = tokio::sync::mpsc::unbounded_channel();
+    let fullscreen_channel = tokio::sync::mpsc::unbounded_channel();
     let config = DynamicLayoutConfigMain::default();
-    let this = DynamicLayout {
+    let inner = DynamicLayoutInner {
         app,
         cancel_minimize: Rc::new(RefCell::new(HashMap::new())),
-        order_managers: Rc::new(RefCell::new(
-            HashMap::<String, Rc<RefCell<CycleOrder>>>::new(),
-        )),
-        activate_widget: (channel.0, Some(channel.1)),
-        deactivate_widget: (channel1.0, Some(channel1.1)),
-        cycle_channel: (channel2.0, Some(channel2.1)),
-        config,
+        order_managers: Rc::new(RefCell::new(HashMap::<
+            String,
+            Rc<RefCell<dyn WidgetOrderManager>>,
+        >::new())),
+        window_instances: RefCell::new(HashMap::new()),
+        activate_widget: activate_widget.0,
+        deactivate_widget: deactivate_widget.0,
+        cycle_channel: cycle_channel.0,
+        promote_widget: promote_widget.0,
+        property_update: property_update.0,
+        idle_reset: idle_reset.0,
+        popup_update: popup_update.0,
+        idle_timers: Rc::new(RefCell::new(HashMap::new())),
+        notification_queues: Rc::new(RefCell::new(HashMap::new())),
+        fullscreen_channel: fullscreen_channel.0,
+        fullscreen_state: Rc::new(RefCell::new(FullscreenState::default())),
+        fullscreen_watcher_started: Cell::new(false),
+        config: Rc::new(RefCell::new(config)),
+        config_source: RefCell::new(String::new()),
+        base_config: RefCell::new(DeDynamicLayoutConfigMain::default()),
+        profile_state: RefCell::new(ProfileState::default()),
+        closed_css_classes: RefCell::new(HashMap::new()),
+        persistent_state: PersistentState::load_shared(),
+        receivers: RefCell::new(Some(EventReceivers {
+            activate_widget: activate_widget.1,
+            deactivate_widget: deactivate_widget.1,
+            cycle_channel: cycle_channel.1,
+            promote_widget: promote_widget.1,
+            property_update: property_update.1,
+            idle_reset: idle_reset.1,
+            popup_update: popup_update.1,
+            fullscreen_channel: fullscreen_channel.1,
+        })),
+    };
+    let this = DynamicLayout {
+        inner: Rc::new(inner),
     };
     ROk(SabiLayoutManager_TO::from_value(this, TD_CanDowncast))
 }
 
 impl SabiLayoutManager for DynamicLayout {
     fn init(&mut self) {
+        let fallback_provider = gtk::CssProvider::new();
+        let css = grass::from_string(include_str!("../default.scss"), &grass::Options::default())
+            .unwrap();
+        fallback_provider.load_from_string(&css);
+        gtk::style_context_add_provider_for_display(
+            &gdk::Display::default().unwrap(),
+            &fallback_provider,
+            gtk::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        );
         self.update_windows();
         self.start_event_listener();
+        self.start_monitor_listener();
+        self.start_profile_timer();
+        let persistent_state = self.persistent_state.clone();
+        self.app.connect_shutdown(move |_| {
+            persistent_state.borrow_mut().flush();
+        });
     }
 
     fn update_config(&mut self, config: RString) -> RResult<(), RBoxError> {
         log::trace!("config: {:#?}", config);
 
-        match serde_json::from_str::<DeDynamicLayoutConfigMain>(&config) {
+        *self.config_source.borrow_mut() = config.to_string();
+        let problems = match serde_json::from_str::<DeDynamicLayoutConfigMain>(&config) {
             Ok(conf) => {
-                self.config = conf.into_main_config();
+                let problems = conf.validate();
+                *self.base_config.borrow_mut() = conf;
+                problems
             }
             Err(err) => {
                 log::error!(
-                    "Failed to parse config into struct, using default: {:#?}",
+                    "Failed to parse config into struct, keeping the previous one: {:#?}",
                     err
                 );
+                return RErr(RBoxError::from_fmt(&format!(
+                    "Failed to parse config, keeping the previous one: {err}"
+                )));
             }
+        };
+        for problem in problems.iter() {
+            log::warn!("invalid config: {problem}");
         }
-        log::debug!("current config: {:#?}", self.config);
-
-        if self.app.windows().first().is_some() {
-            self.update_windows();
-            for window_name in self.config.windows.keys() {
-                let window = self
-                    .order_managers
-                    .borrow()
-                    .get(window_name)
-                    .unwrap()
-                    .borrow()
-                    .get_window();
-                self.config
-                    .get_for_window(window_name)
-                    .window_position
-                    .reconfigure_window(&window);
-            }
-        }
-        for ord in self.order_managers.borrow().iter() {
-            self.configure_container(&ord.0);
-            ord.1.borrow_mut().update_config(
-                self.config.max_active,
-                self.config.get_for_window(ord.0).max_activities,
-            );
-            if self.config.get_for_window(ord.0).reorder_on_reload {
-                Self::update_activity_order(ord.1, &self.config.get_for_window(ord.0));
-            }
+        self.update_profile(true);
 
-            for widget_id in ord.1.borrow().list_activities() {
-                self.configure_widget(&widget_id);
-            }
+        if !problems.is_empty() {
+            return RErr(RBoxError::from_fmt(&format_problems(&problems)));
         }
-
         ROk(())
     }
 
@@ -162,16 +251,23 @@ impl SabiLayoutManager for DynamicLayout {
         let order_manager = self.order_managers.borrow();
         let ord = order_manager.get(window_name.as_str()).unwrap();
         ord.borrow_mut().add(activity_id, widget.clone());
-        let config = self.config.get_for_window(window_name.as_str());
+        let config = self.config_for_window(window_name.as_str());
         if config.reorder_on_add {
             if old_mode == ActivityMode::Compact {
-                ord.borrow_mut().activate(activity_id);
+                activate_and_refresh(ord, activity_id);
             }
             Self::update_activity_order(ord, &config);
         }
+        self.persistent_state
+            .borrow()
+            .restore_order(&window_name, &mut *ord.borrow_mut());
+        self.persistent_state
+            .borrow_mut()
+            .save_order(&window_name, &*ord.borrow());
 
         self.configure_widget(activity_id);
         widget.remove_css_class("being-added");
+        self.reset_idle_collapse(&window_name);
     }
 
     fn get_activity(&self, activity: &ActivityIdentifier) -> ROption<SabiWidget> {
@@ -221,45 +317,22 @@ impl SabiLayoutManager for DynamicLayout {
         mode_id: u8,
         duration: ROption<u64>,
     ) {
-        if let Some((widget, window_name)) =
-            Self::find_widget(&self.order_managers.borrow(), activity)
-        {
+        if let Some((_, window_name)) = Self::find_widget(&self.order_managers.borrow(), activity) {
             let mode = ActivityMode::try_from(mode_id).unwrap();
-            let priority = self
-                .order_managers
-                .borrow()
-                .get(&window_name)
-                .unwrap()
-                .clone();
-            if !priority.borrow().is_shown(activity) {
-                widget.set_visible(true);
-                widget.remove_css_class("hidden");
-            }
-            widget.set_mode(mode);
-            // if matches!(mode, ActivityMode::Minimal | ActivityMode::Compact) {
-            //     return;
-            // }
+            Self::promote_activity(&self.order_managers, activity);
+            self.reset_idle_collapse(&window_name);
             let timeout = duration.unwrap_or(
-                self.config
-                    .get_for_window(&window_name)
+                self.config_for_window(&window_name)
                     .auto_minimize_timeout
                     .try_into()
                     .unwrap_or(config::DEFAULT_AUTO_MINIMIZE_TIMEOUT as u64),
             );
-            let widget = widget.clone();
-            let activity = activity.clone();
-            glib::timeout_add_local_once(
-                Duration::from_millis(timeout.try_into().unwrap()),
-                move || {
-                    if priority.borrow().is_active(&activity) {
-                        widget.set_mode(ActivityMode::Compact);
-                    } else {
-                        widget.set_mode(ActivityMode::Minimal);
-                    }
-                    if !priority.borrow().is_shown(&activity) {
-                        widget.add_css_class("hidden");
-                        widget.size_allocate(&gdk::Rectangle::new(0, 0, 50, 40), 0);
-                    }
+            self.queue_notification(
+                &window_name,
+                Notification {
+                    activity: activity.clone(),
+                    mode,
+                    duration: Duration::from_millis(timeout),
                 },
             );
         } else {
@@ -282,7 +355,21 @@ r"Commands:
     remove-css [window name (default if none)] <CSS class>
     show [window name (default if none)]
     hide [window name (default if none)]
-    toggle [window name (default if none)]"
+    toggle [window name (default if none)]
+    activate <activity>
+    deactivate <activity>
+    set-mode <activity> <minimal|compact|expanded|overlay>
+    next [window name (default if none)]
+    previous [window name (default if none)]
+    focus <activity>
+    state [--json]
+    move <activity> [window name (the activity's own window if none)]
+    set-limits [window name (default if none)] <max active> <max activities>
+    export-order [window name (default if none)]
+    validate
+    profile [profile name|none|auto]
+
+    <activity> can be `activity@module` or only `activity` if there is only one activity with that name"
                 .into());
             }
             "add-css" => {
@@ -291,13 +378,15 @@ r"Commands:
                     2 => (words[0], words[1]),
                     _ => return RErr(RBoxError::from_fmt("add-css requires 1(CSS class for default window) or 2(window name, CSS class) arguments")),
                 };
-                let ords = self.order_managers.borrow();
-                let ord = match ords.get(window_name) {
-                    Some(ord) => ord,
+                let ord = match self.order_managers.borrow().get(window_name) {
+                    Some(ord) => ord.clone(),
                     None => return RErr(RBoxError::from_fmt("Window not found")),
                 };
-                match ord.borrow_mut().add_css_class(css_class) {
+                let changed = ord.borrow_mut().add_css_class(css_class);
+                match changed {
                     true => {
+                        // a profile rule can depend on the class
+                        self.update_profile(false);
                         return ROk("CSS class added".into());
                     }
                     false => {
@@ -311,13 +400,15 @@ r"Commands:
                     2 => (words[0], words[1]),
                     _ => return RErr(RBoxError::from_fmt("remove-css requires 1(CSS class for default window) or 2(window name, CSS class) arguments")),
                 };
-                let ords = self.order_managers.borrow();
-                let ord = match ords.get(window_name) {
-                    Some(ord) => ord,
+                let ord = match self.order_managers.borrow().get(window_name) {
+                    Some(ord) => ord.clone(),
                     None => return RErr(RBoxError::from_fmt("Window not found")),
                 };
-                match ord.borrow_mut().remove_css_class(css_class) {
+                let changed = ord.borrow_mut().remove_css_class(css_class);
+                match changed {
                     true => {
+                        // a profile rule can depend on the class
+                        self.update_profile(false);
                         return ROk("CSS class removed".into());
                     }
                     false => {
@@ -341,6 +432,11 @@ r"Commands:
                     None => return RErr(RBoxError::from_fmt("Window not found")),
                 };
                 let window = ord.borrow().get_window();
+                // the user chooses now, it's not shown again when the fullscreen window closes
+                self.fullscreen_state
+                    .borrow_mut()
+                    .hidden
+                    .remove(&window_name);
                 if !window.is_visible() {
                     window.present();
                     return ROk("Window is now shown".into());
@@ -364,6 +460,10 @@ r"Commands:
                     None => return RErr(RBoxError::from_fmt("Window not found")),
                 };
                 let window = ord.borrow().get_window();
+                self.fullscreen_state
+                    .borrow_mut()
+                    .hidden
+                    .remove(&window_name);
                 if window.is_visible() {
                     window.set_visible(false);
                     return ROk("Window is now hidden".into());
@@ -387,6 +487,10 @@ r"Commands:
                     None => return RErr(RBoxError::from_fmt("Window not found")),
                 };
                 let window = ord.borrow().get_window();
+                self.fullscreen_state
+                    .borrow_mut()
+                    .hidden
+                    .remove(&window_name);
                 if window.is_visible() {
                     window.set_visible(false);
                     return ROk("Window is now hidden".into());
@@ -395,6 +499,299 @@ r"Commands:
                     return ROk("Window is now shown".into());
                 }
             }
+            "activate" | "deactivate" => {
+                if words.len() != 1 {
+                    return RErr(RBoxError::from_fmt(&format!(
+                        "{command} requires 1(activity) argument"
+                    )));
+                }
+                let (id, ord) = match self.find_activity(words[0]) {
+                    Ok(found) => found,
+                    Err(err) => return RErr(err),
+                };
+                if command == "activate" {
+                    if !activate_and_refresh(&ord, &id) {
+                        return RErr(activation_error(&*ord.borrow(), &id));
+                    }
+                } else if !ord.borrow_mut().deactivate(&id) {
+                    return RErr(RBoxError::from_fmt("Activity is not active"));
+                }
+                self.save_order(&ord);
+                if command == "activate" {
+                    return ROk("Activity activated".into());
+                } else {
+                    return ROk("Activity deactivated".into());
+                }
+            }
+            "set-mode" => {
+                if words.len() != 2 {
+                    return RErr(RBoxError::from_fmt(
+                        "set-mode requires 2(activity, mode) arguments",
+                    ));
+                }
+                let mode = match parse_activity_mode(words[1]) {
+                    Some(mode) => mode,
+                    None => {
+                        return RErr(RBoxError::from_fmt(
+                            "Invalid mode, use minimal, compact, expanded or overlay",
+                        ))
+                    }
+                };
+                let (id, ord) = match self.find_activity(words[0]) {
+                    Ok(found) => found,
+                    Err(err) => return RErr(err),
+                };
+                if mode == ActivityMode::Minimal {
+                    ord.borrow_mut().deactivate(&id);
+                } else if !ord.borrow().is_active(&id) && !activate_and_refresh(&ord, &id) {
+                    return RErr(activation_error(&*ord.borrow(), &id));
+                }
+                let widget = ord
+                    .borrow()
+                    .get_widget_map()
+                    .borrow()
+                    .get(&id)
+                    .unwrap()
+                    .clone();
+                widget.set_mode(mode);
+                self.save_order(&ord);
+                return ROk("Mode set".into());
+            }
+            "next" | "previous" => {
+                let window_name = match words.len() {
+                    0 => "".to_string(),
+                    1 => words[0].to_string(),
+                    _ => {
+                        return RErr(RBoxError::from_fmt(&format!(
+                            "{command} requires 0(default window) or 1(window name) argument"
+                        )));
+                    }
+                };
+                let ords = self.order_managers.borrow();
+                let ord = match ords.get(&window_name) {
+                    Some(ord) => ord,
+                    None => return RErr(RBoxError::from_fmt("Window not found")),
+                };
+                let cycled = if command == "next" {
+                    ord.borrow_mut().next()
+                } else {
+                    ord.borrow_mut().previous()
+                };
+                if !cycled {
+                    return RErr(RBoxError::from_fmt(
+                        "The window has no activities or its order strategy can't cycle them",
+                    ));
+                }
+                self.persistent_state
+                    .borrow_mut()
+                    .save_order(&window_name, &*ord.borrow());
+                return ROk("Activities cycled".into());
+            }
+            "focus" => {
+                if words.len() != 1 {
+                    return RErr(RBoxError::from_fmt("focus requires 1(activity) argument"));
+                }
+                let (id, ord) = match self.find_activity(words[0]) {
+                    Ok(found) => found,
+                    Err(err) => return RErr(err),
+                };
+                if !ord.borrow().is_shown(&id) {
+                    Self::promote_activity(&self.order_managers, &id);
+                }
+                let mut message = "Activity focused";
+                if !ord.borrow().is_active(&id) && !activate_and_refresh(&ord, &id) {
+                    if !ord.borrow().is_shown(&id) {
+                        return RErr(activation_error(&*ord.borrow(), &id));
+                    }
+                    message = "Activity focused, it can't be activated because max_active is 0";
+                }
+                let window = ord.borrow().get_window();
+                if !window.is_visible() {
+                    window.present();
+                }
+                let widget = ord
+                    .borrow()
+                    .get_widget_map()
+                    .borrow()
+                    .get(&id)
+                    .unwrap()
+                    .clone();
+                widget.grab_focus();
+                self.save_order(&ord);
+                return ROk(message.into());
+            }
+            "move" => {
+                let (activity, window_name) = match words.len() {
+                    1 => (words[0], None),
+                    2 => (words[0], Some(words[1])),
+                    _ => {
+                        return RErr(RBoxError::from_fmt(
+                            "move requires 1(activity, to move it back to its window) or 2(activity, window name) arguments",
+                        ));
+                    }
+                };
+                let (id, _) = match self.find_activity(activity) {
+                    Ok(found) => found,
+                    Err(err) => return RErr(err),
+                };
+                if let Some(window_name) = window_name {
+                    if !self.order_managers.borrow().contains_key(window_name) {
+                        return RErr(RBoxError::from_fmt("Window not found"));
+                    }
+                }
+                {
+                    let mut persistent_state = self.persistent_state.borrow_mut();
+                    match window_name {
+                        Some(window_name) => {
+                            persistent_state
+                                .window_overrides
+                                .insert(activity_key(&id), window_name.to_string());
+                        }
+                        None => {
+                            persistent_state.window_overrides.remove(&activity_key(&id));
+                        }
+                    }
+                    persistent_state.save();
+                }
+                let (widget, current_window) =
+                    Self::find_widget(&self.order_managers.borrow(), &id).unwrap();
+                let window_name = self.get_window_name(&id);
+                if window_name == current_window {
+                    return ROk("Activity is already in that window".into());
+                }
+                self.move_activity(&id, widget, &window_name);
+                return ROk("Activity moved".into());
+            }
+            "set-limits" => {
+                let (window_name, max_active, max_activities) = match words.len() {
+                    2 => ("", words[0], words[1]),
+                    3 => (words[0], words[1], words[2]),
+                    _ => return RErr(RBoxError::from_fmt("set-limits requires 2(max active, max activities for default window) or 3(window name, max active, max activities) arguments")),
+                };
+                let (max_active, max_activities) =
+                    match (max_active.parse::<u16>(), max_activities.parse::<u16>()) {
+                        (Ok(max_active), Ok(max_activities)) => (max_active, max_activities),
+                        _ => {
+                            return RErr(RBoxError::from_fmt(
+                                "max active and max activities must be non-negative numbers",
+                            ));
+                        }
+                    };
+                let ords = self.order_managers.borrow();
+                let ord = match ords.get(window_name) {
+                    Some(ord) => ord,
+                    None => return RErr(RBoxError::from_fmt("Window not found")),
+                };
+                ord.borrow_mut().update_config(max_active, max_activities);
+                self.persistent_state
+                    .borrow_mut()
+                    .save_order(window_name, &*ord.borrow());
+                return ROk("Limits updated".into());
+            }
+            "export-order" => {
+                let window_name = match words.len() {
+                    0 => "".to_string(),
+                    1 => words[0].to_string(),
+                    _ => {
+                        return RErr(RBoxError::from_fmt(
+                            "export-order requires 0(default window) or 1(window name) argument",
+                        ));
+                    }
+                };
+                let ords = self.order_managers.borrow();
+                let ord = match ords.get(&window_name) {
+                    Some(ord) => ord,
+                    None => return RErr(RBoxError::from_fmt("Window not found")),
+                };
+                let order = match self.persistent_state.borrow().suggestions.get(&window_name) {
+                    Some(order) => order.clone(),
+                    None => ord
+                        .borrow()
+                        .list_activities()
+                        .iter()
+                        .map(|id| activity_key(id))
+                        .collect(),
+                };
+                let mut exported = String::from("activity_order: [\n");
+                for key in order {
+                    exported.push_str(&format!("    \"{key}\",\n"));
+                }
+                exported.push_str("],");
+                return ROk(exported.into());
+            }
+            "profile" => {
+                let choice = match words.as_slice() {
+                    [] => {
+                        let state = self.profile_state.borrow();
+                        let active = state.active.as_deref().unwrap_or("none");
+                        return match state.choice {
+                            ProfileChoice::Rules => ROk(format!("{active} (auto)").into()),
+                            ProfileChoice::Manual(_) => ROk(active.into()),
+                        };
+                    }
+                    ["auto"] => ProfileChoice::Rules,
+                    ["none"] => ProfileChoice::Manual(None),
+                    [profile] => {
+                        if !self.base_config.borrow().profiles.contains_key(*profile) {
+                            return RErr(RBoxError::from_fmt(&format!(
+                                "Profile {profile} not found"
+                            )));
+                        }
+                        ProfileChoice::Manual(Some(profile.to_string()))
+                    }
+                    _ => {
+                        return RErr(RBoxError::from_fmt(
+                            "profile requires 0 or 1(profile name, none or auto) argument",
+                        ));
+                    }
+                };
+                self.profile_state.borrow_mut().choice = choice;
+                self.update_profile(false);
+                let active = self.profile_state.borrow().active.clone();
+                return ROk(format!("Profile: {}", active.as_deref().unwrap_or("none")).into());
+            }
+            "validate" => {
+                if !words.is_empty() {
+                    return RErr(RBoxError::from_fmt("validate requires 0 arguments"));
+                }
+                let (problems, warnings) = match serde_json::from_str::<DeDynamicLayoutConfigMain>(
+                    &self.config_source.borrow(),
+                ) {
+                    Ok(conf) => (conf.validate(), conf.warnings()),
+                    Err(err) => (
+                        vec![format!("failed to parse the config: {err}")],
+                        Vec::new(),
+                    ),
+                };
+                if problems.is_empty() {
+                    let mut message = String::from("The config is valid");
+                    message.push_str(&format_warnings(&warnings));
+                    return ROk(message.into());
+                }
+                let mut message = format_problems(&problems);
+                message.push_str(&format_warnings(&warnings));
+                return RErr(RBoxError::from_fmt(&message));
+            }
+            "state" => {
+                let json = match words.as_slice() {
+                    [] => false,
+                    ["--json"] => true,
+                    _ => {
+                        return RErr(RBoxError::from_fmt("state requires 0 arguments or --json"));
+                    }
+                };
+                let state = LayoutState::from_order_managers(&self.order_managers.borrow());
+                let state = if json {
+                    serde_json::to_string(&state).map_err(RBoxError::new)
+                } else {
+                    ron::ser::to_string_pretty(&state, PrettyConfig::default())
+                        .map_err(RBoxError::new)
+                };
+                match state {
+                    Ok(state) => return ROk(state.into()),
+                    Err(err) => return RErr(err),
+                }
+            }
             _ => {
                 return RErr(RBoxError::from_fmt(
                     "Unknown command, use 'layout help' for a list of commands",
@@ -405,7 +802,74 @@ r"Commands:
 }
 
 impl DynamicLayout {
+    /// Applies `self.config` to the windows and to the activities
+    pub(crate) fn apply_config(&self) {
+        log::debug!("current config: {:#?}", self.config.borrow());
+
+        // the activities are put back in compact mode anyway
+        for ord in self.order_managers.borrow().values() {
+            close_window_popup(&mut *ord.borrow_mut());
+        }
+
+        if self.app.windows().first().is_some() {
+            self.update_windows();
+        }
+        let windows: Vec<String> = self.order_managers.borrow().keys().cloned().collect();
+        for window_name in windows {
+            self.update_order_strategy(&window_name);
+        }
+        for ord in self.order_managers.borrow().iter() {
+            self.configure_container(&ord.0);
+            let config = self.config_for_window(ord.0);
+            ord.1
+                .borrow_mut()
+                .update_config(config.max_active, config.max_activities);
+            ord.1
+                .borrow_mut()
+                .set_promotion_decay(Duration::from_millis(config.promotion_decay));
+            if config.reorder_on_reload {
+                Self::update_activity_order(ord.1, &config);
+            }
+
+            for widget_id in ord.1.borrow().list_activities() {
+                self.configure_widget(&widget_id);
+            }
+            self.persistent_state
+                .borrow_mut()
+                .save_order(ord.0, &*ord.1.borrow());
+        }
+        let windows: Vec<String> = self.order_managers.borrow().keys().cloned().collect();
+        for window_name in windows {
+            self.reset_idle_collapse(&window_name);
+        }
+        self.start_fullscreen_watcher();
+        self.update_fullscreen();
+    }
+
+    /// Returns the config of the window in the config that `window_name` was created for
+    pub(crate) fn config_for_window(&self, window_name: &str) -> DynamicLayoutConfig {
+        let config = self.config.borrow();
+        match self.window_instances.borrow().get(window_name) {
+            Some(instance) => config.get_for_window(&instance.config_name),
+            None => config.get_for_window(window_name),
+        }
+    }
+
+    /// Returns the window where `activity_id` goes: the one chosen with `move`, then the one
+    /// requested by the module, then the default window. A config window created for more
+    /// monitors only gets the activities in its first instance, the others (`name:CONNECTOR`)
+    /// have to be chosen by name
     fn get_window_name(&self, activity_id: &ActivityIdentifier) -> String {
+        if let Some(window_name) = self
+            .persistent_state
+            .borrow()
+            .window_overrides
+            .get(&activity_key(activity_id))
+        {
+            if self.order_managers.borrow().contains_key(window_name) {
+                return window_name.clone();
+            }
+        }
         let requested_window = activity_id.metadata().window_name().unwrap_or_default();
         if self.order_managers.borrow().contains_key(&requested_window) {
             requested_window
@@ -413,11 +877,48 @@ impl DynamicLayout {
             "".to_string()
         }
     }
-    fn start_event_listener(&mut self) {
+    /// Finds an activity by `activity@module` or only by `activity` if it's not ambiguous,
+    /// returns the activity and the order manager of the window it's in
+    pub(crate) fn find_activity(
+        &self,
+        name: &str,
+    ) -> std::result::Result<(ActivityIdentifier, Rc<RefCell<dyn WidgetOrderManager>>), RBoxError>
+    {
+        let (activity, module) = match name.split_once('@') {
+            Some((activity, module)) => (activity, Some(module)),
+            None => (name, None),
+        };
+        let mut found = Vec::new();
+        for ord in self.order_managers.borrow().values() {
+            for id in ord.borrow().list_activities() {
+                let module_matches = match module {
+                    Some(module) => id.module().eq_ignore_ascii_case(module),
+                    None => true,
+                };
+                if module_matches && id.activity().eq_ignore_ascii_case(activity) {
+                    found.push(((*id).clone(), ord.clone()));
+                }
+            }
+        }
+        match found.len() {
+            0 => Err(RBoxError::from_fmt(&format!("Activity {name} not found"))),
+            1 => Ok(found.remove(0)),
+            _ => Err(RBoxError::from_fmt(&format!(
+                "Activity name {name} is ambiguous, use activity@module"
+            ))),
+        }
+    }
+
+    fn start_event_listener(&self) {
+        let receivers = match self.receivers.borrow_mut().take() {
+            Some(receivers) => receivers,
+            None => return,
+        };
         // listen to activate widget
-        let mut recv_activate_widget = self.activate_widget.1.take().unwrap();
+        let mut recv_activate_widget = receivers.activate_widget;
         // let widget_map = self.widget_map.clone();
         let order_managers = self.order_managers.clone();
+        let persistent_state = self.persistent_state.clone();
         // let container = self.container.clone().unwrap();
         glib::MainContext::default().spawn_local(async move {
             while let Some(id) = recv_activate_widget.recv().await {
@@ -425,7 +926,10 @@ impl DynamicLayout {
                 let (_, window_name) = Self::find_widget(&order_managers.borrow(), &id).unwrap();
                 let order_managers = &order_managers.borrow();
                 let ord = order_managers.get(&window_name).unwrap();
-                ord.borrow_mut().activate(&id);
+                activate_and_refresh(ord, &id);
+                persistent_state
+                    .borrow_mut()
+                    .save_order(&window_name, &*ord.borrow());
                 log::trace!("activate {id}");
                 // activate and show this
                 // let aw = widget_map.get(&id).unwrap();
@@ -436,9 +940,10 @@ impl DynamicLayout {
         });
 
         // listen to deactivate widget
-        let mut recv_deactivate_widget = self.deactivate_widget.1.take().unwrap();
+        let mut recv_deactivate_widget = receivers.deactivate_widget;
         // let widget_map = self.widget_map.clone();
         let order_managers = self.order_managers.clone();
+        let persistent_state = self.persistent_state.clone();
         // let container = self.container.clone().unwrap();
         glib::MainContext::default().spawn_local(async move {
             while let Some(id) = recv_deactivate_widget.recv().await {
@@ -447,16 +952,92 @@ impl DynamicLayout {
                 let order_managers = &order_managers.borrow();
                 let ord = order_managers.get(&window_name).unwrap();
                 ord.borrow_mut().deactivate(&id);
+                persistent_state
+                    .borrow_mut()
+                    .save_order(&window_name, &*ord.borrow());
                 log::trace!("deactivate {id}");
                 // let aw = widget_map.get(&id);
                 // update.apply(widget_map, &container, &id);
             }
         });
 
+        // listen to promote widget
+        let mut recv_promote_widget = receivers.promote_widget;
+        let order_managers = self.order_managers.clone();
+        glib::MainContext::default().spawn_local(async move {
+            while let Some(id) = recv_promote_widget.recv().await {
+                Self::promote_activity(&order_managers, &id);
+                log::trace!("promote {id}");
+            }
+        });
+
+        // listen to property updates
+        let mut recv_property_update = receivers.property_update;
+        let this = self.clone();
+        glib::MainContext::default().spawn_local(async move {
+            // activity -> last time a property update promoted it
+            let mut last_promotions = HashMap::<ActivityIdentifier, Instant>::new();
+            while let Some(id) = recv_property_update.recv().await {
+                let window_name = match Self::find_widget(&this.order_managers.borrow(), &id) {
+                    Some((_, window_name)) => window_name,
+                    None => continue,
+                };
+                let interval = this
+                    .config_for_window(&window_name)
+                    .update_promotion_interval;
+                if interval < 0 {
+                    continue;
+                }
+                let now = Instant::now();
+                if last_promotions.get(&id).is_some_and(|last| {
+                    now.duration_since(*last) < Duration::from_millis(interval as u64)
+                }) {
+                    continue;
+                }
+                last_promotions.insert(id.clone(), now);
+                Self::promote_activity(&this.order_managers, &id);
+                log::trace!("promote {id} after a property update");
+            }
+        });
+
+        // listen to idle resets
+        let mut recv_idle_reset = receivers.idle_reset;
+        let this = self.clone();
+        glib::MainContext::default().spawn_local(async move {
+            while let Some(id) = recv_idle_reset.recv().await {
+                let window_name = match Self::find_widget(&this.order_managers.borrow(), &id) {
+                    Some((_, window_name)) => window_name,
+                    None => continue,
+                };
+                this.reset_idle_collapse(&window_name);
+            }
+        });
+
+        // listen to mode changes
+        let mut recv_popup_update = receivers.popup_update;
+        let this = self.clone();
+        glib::MainContext::default().spawn_local(async move {
+            while let Some(id) = recv_popup_update.recv().await {
+                this.update_popup(&id);
+            }
+        });
+
+        // listen to fullscreen outputs
+        let mut recv_fullscreen = receivers.fullscreen_channel;
+        let this = self.clone();
+        glib::MainContext::default().spawn_local(async move {
+            while let Some(outputs) = recv_fullscreen.recv().await {
+                log::trace!("fullscreen outputs {:?}", outputs);
+                this.fullscreen_state.borrow_mut().outputs = outputs;
+                this.update_fullscreen();
+            }
+        });
+
         // listen to cycle widgets
-        let mut recv_cycle = self.cycle_channel.1.take().unwrap();
+        let mut recv_cycle = receivers.cycle_channel;
         // let widget_map = self.widget_map.clone();
         let order_managers = self.order_managers.clone();
+        let persistent_state = self.persistent_state.clone();
         // let container = self.container.clone().unwrap();
         glib::MainContext::default().spawn_local(async move {
             while let Some((window_name, next)) = recv_cycle.recv().await {
@@ -481,6 +1062,9 @@ impl DynamicLayout {
                     //     .unwrap();
                     // (updates, last_id)
                 };
+                persistent_state
+                    .borrow_mut()
+                    .save_order(&window_name, &*ord.borrow());
                 log::trace!("cycle {:#?}", ord.borrow());
                 // let aw = widget_map.get(&id);
 
@@ -491,8 +1075,201 @@ impl DynamicLayout {
         });
     }
 
+    /// Creates and removes the windows when monitors are connected or disconnected
+    fn start_monitor_listener(&self) {
+        let display = match gdk::Display::default() {
+            Some(display) => display,
+            None => return,
+        };
+        let this = self.clone();
+        display.monitors().connect_items_changed(move |_, _, _, _| {
+            let this = this.clone();
+            // wait for the new monitors to be fully configured
+            glib::idle_add_local_once(move || {
+                log::debug!("monitors changed");
+                // a profile rule can depend on the monitors
+                if !this.update_profile(false) {
+                    this.update_windows();
+                }
+            });
+        });
+    }
+
+    /// Keeps the `auto` exclusive zone equal to the size of the window when it changes
+    fn watch_exclusive_zone(&self, window: &gtk::Window, window_name: &str) {
+        let this = self.clone();
+        let window_name = window_name.to_string();
+        window.connect_realize(move |window| {
+            let surface = match window.surface() {
+                Some(surface) => surface,
+                None => return,
+            };
+            let this = this.clone();
+            let window_name = window_name.clone();
+            let window = window.downgrade();
+            surface.connect_layout(move |_, width, height| {
+                let window = match window.upgrade() {
+                    Some(window) => window,
+                    None => return,
+                };
+                let position = this.config_for_window(&window_name).window_position;
+                if !position.layer_shell || position.exclusive_zone != ExclusiveZone::Auto {
+                    return;
+                }
+                let zone = position.exclusive_zone_for(width, height);
+                if window.exclusive_zone() != zone {
+                    window.set_exclusive_zone(zone);
+                }
+            });
+        });
+    }
+
+    /// Expands the window if it's collapsed and restarts the timer that collapses it
+    fn reset_idle_collapse(&self, window_name: &str) {
+        let ord = match self.order_managers.borrow().get(window_name) {
+            Some(ord) => ord.clone(),
+            None => return,
+        };
+        if let Some(source) = self.idle_timers.borrow_mut().remove(window_name) {
+            source.remove();
+        }
+        if ord.borrow().is_collapsed() {
+            set_collapsed(&mut *ord.borrow_mut(), false);
+        }
+        let timeout = self.config_for_window(window_name).idle_collapse_timeout;
+        if timeout < 0 {
+            return;
+        }
+        let order_managers = self.order_managers.clone();
+        let idle_timers1 = self.idle_timers.clone();
+        let window_name1 = window_name.to_string();
+        let source = glib::timeout_add_local_once(
+            Duration::from_millis(timeout.try_into().unwrap()),
+            move || {
+                idle_timers1.borrow_mut().remove(&window_name1);
+                let ord = match order_managers.borrow().get(&window_name1) {
+                    Some(ord) => ord.clone(),
+                    None => return,
+                };
+                let container = ord.borrow().get_container();
+                if container.state_flags().contains(StateFlags::PRELIGHT) {
+                    // the timer is restarted when the pointer leaves
+                    return;
+                }
+                log::trace!("collapsing idle window {}", window_name1);
+                set_collapsed(&mut *ord.borrow_mut(), true);
+            },
+        );
+        self.idle_timers
+            .borrow_mut()
+            .insert(window_name.to_string(), source);
+    }
+
+    /// Starts watching the fullscreen windows the first time a window needs it
+    fn start_fullscreen_watcher(&self) {
+        if self.fullscreen_watcher_started.get() {
+            return;
+        }
+        let needed = self
+            .config
+            .borrow()
+            .windows
+            .values()
+            .any(|config| config.fullscreen != FullscreenMode::None);
+        if !needed {
+            return;
+        }
+        match IpcBackend::from_env() {
+            Some(backend) => {
+                log::debug!("watching fullscreen windows with {:?}", backend);
+                backend.spawn_watcher(self.fullscreen_channel.clone());
+            }
+            None => {
+                log::warn!("fullscreen is only supported on sway, i3 and Hyprland");
+            }
+        }
+        self.fullscreen_watcher_started.set(true);
+    }
+
+    /// Hides or lowers the windows on outputs with a fullscreen window
+    /// and restores the other ones
+    fn update_fullscreen(&self) {
+        let mut state = self.fullscreen_state.borrow_mut();
+        let state = &mut *state;
+        for (window_name, ord) in self.order_managers.borrow().iter() {
+            let window = ord.borrow().get_window();
+            let window_config = self.config_for_window(window_name);
+            // a hidden window is not on any monitor, use the one it was on
+            let connector = match state.hidden.get(window_name) {
+                Some(connector) => Some(connector.clone()),
+                None => window_connector(&window),
+            };
+            let fullscreen = match &connector {
+                Some(connector) => state.outputs.contains(connector),
+                None => false,
+            };
+            let layer_shell = window_config.window_position.layer_shell;
+            match (window_config.fullscreen, connector) {
+                (FullscreenMode::Hide, Some(connector)) if fullscreen => {
+                    if window.is_visible() {
+                        state.hidden.insert(window_name.clone(), connector);
+                        window.set_visible(false);
+                    }
+                }
+                (FullscreenMode::Bottom, _) if fullscreen => {
+                    if layer_shell {
+                        state.lowered.insert(window_name.clone());
+                        window.set_layer(gtk_layer_shell::Layer::Bottom);
+                    }
+                }
+                _ => {
+                    // only the windows hidden or lowered here are restored,
+                    // a window without its monitor stays hidden
+                    if state.hidden.remove(window_name).is_some()
+                        && !window.is_visible()
+                        && !window.has_css_class("no-monitor")
+                    {
+                        window.present();
+                    }
+                    if state.lowered.remove(window_name) && layer_shell {
+                        window.set_layer(window_config.window_position.layer.map_gtk());
+                    }
+                }
+            }
+        }
+    }
+
+    /// Saves the order of the window managed by `ord`
+    fn save_order(&self, ord: &Rc<RefCell<dyn WidgetOrderManager>>) {
+        let window_name = self
+            .order_managers
+            .borrow()
+            .iter()
+            .find(|(_, other)| Rc::ptr_eq(other, ord))
+            .map(|(window_name, _)| window_name.clone());
+        if let Some(window_name) = window_name {
+            self.persistent_state
+                .borrow_mut()
+                .save_order(&window_name, &*ord.borrow());
+        }
+    }
+
+    /// Promotes `activity` in its window and refreshes the order when the promotion expires
+    fn promote_activity(
+        order_managers: &Rc<RefCell<HashMap<String, Rc<RefCell<dyn WidgetOrderManager>>>>>,
+        activity: &ActivityIdentifier,
+    ) {
+        let window_name = match Self::find_widget(&order_managers.borrow(), activity) {
+            Some((_, window_name)) => window_name,
+            None => return,
+        };
+        let ord = order_managers.borrow().get(&window_name).unwrap().clone();
+        let refresh_after = ord.borrow_mut().promote(activity);
+        schedule_refresh(&ord, refresh_after);
+    }
+
     pub(super) fn find_widget(
-        order_managers: &HashMap<String, Rc<RefCell<CycleOrder>>>,
+        order_managers: &HashMap<String, Rc<RefCell<dyn WidgetOrderManager>>>,
         activity: &ActivityIdentifier,
     ) -> Option<(ActivityWidget, String)> {
         for (window_name, ord) in order_managers.iter() {
@@ -505,12 +1282,43 @@ impl DynamicLayout {
         None
     }
 
-    fn update_windows(&mut self) {
+    fn update_windows(&self) {
+        let instances = self.config.borrow().window_instances();
+        {
+            let previous = self.window_instances.borrow();
+            for instance in instances.iter().filter(|instance| instance.unmatched) {
+                // warn only when it starts happening, not every time the windows are updated
+                if previous
+                    .get(&instance.name)
+                    .is_some_and(|previous| previous.unmatched)
+                {
+                    continue;
+                }
+                let position = self
+                    .config
+                    .borrow()
+                    .get_for_window(&instance.config_name)
+                    .window_position;
+                log::warn!(
+                    "no monitor matches {:?}, using the {:?} policy",
+                    position.monitor,
+                    position.unmatched_monitor
+                );
+            }
+        }
+        *self.window_instances.borrow_mut() = instances
+            .iter()
+            .map(|instance| (instance.name.clone(), instance.clone()))
+            .collect();
         let mut orphan_widgets: Vec<(ActivityIdentifier, ActivityWidget)> = Vec::new();
-        // remove windows that are no longer in the config
+        // remove windows that are no longer in the config or whose monitor was disconnected
         let mut windows_to_remove: Vec<String> = Vec::new();
         for (window_name, ord) in self.order_managers.borrow().iter() {
-            if !self.config.windows.contains_key(&window_name.to_string()) {
+            if !instances
+                .iter()
+                .any(|instance| instance.name == *window_name)
+            {
+                close_window_popup(&mut *ord.borrow_mut());
                 let mut widgets = Vec::new();
                 for child in ord
                     .borrow()
@@ -535,25 +1343,30 @@ impl DynamicLayout {
                     ord.borrow().get_container().remove(&widget);
                 }
                 windows_to_remove.push(window_name.clone());
-                ord.borrow().window.close();
+                self.close_window(window_name, &*ord.borrow());
             }
         }
         for window_name in windows_to_remove {
             self.order_managers.borrow_mut().remove(&window_name);
-            log::trace!("removing window no longer in config {}", window_name);
+            log::trace!("removing window no longer needed {}", window_name);
         }
-        // create new windows
-        let existing_windows: Vec<String> = self.order_managers.borrow().keys().cloned().collect();
-        let mut windows_to_create: Vec<String> = Vec::new();
-        for window_name in self.config.windows.keys() {
-            if !existing_windows.contains(window_name) {
-                windows_to_create.push(window_name.clone());
+        // create new windows and move the existing ones to their monitor
+        for instance in instances.iter() {
+            let ord = self.order_managers.borrow().get(&instance.name).cloned();
+            match ord {
+                Some(ord) => {
+                    let window = ord.borrow().get_window();
+                    self.config_for_window(&instance.name)
+                        .window_position
+                        .reconfigure_window(&window, instance.monitor.as_ref());
+                    instance.update_visibility(&window);
+                }
+                None => {
+                    log::trace!("creating new window {}", instance.name);
+                    self.create_new_window(&instance.name);
+                }
             }
         }
-        for window_name in windows_to_create {
-            log::trace!("creating new window {}", window_name);
-            self.create_new_window(&window_name);
-        }
         for (widget_id, widget) in orphan_widgets {
             let window_name = self.get_window_name(&widget_id);
             self.order_managers
@@ -564,41 +1377,35 @@ impl DynamicLayout {
                 .add(&widget_id, widget);
             log::trace!("readding orphaned widget {}", widget_id);
         }
-        let mut to_update: Vec<(ActivityIdentifier, ActivityWidget)> = Vec::new();
+        let mut to_update: Vec<(ActivityIdentifier, ActivityWidget, String)> = Vec::new();
         for (current_window, ord) in self.order_managers.borrow().iter() {
             for (id, widget) in ord.borrow().get_widget_map().borrow().iter() {
-                if let Some(desired_window) = id.metadata().window_name() {
-                    if desired_window != *current_window
-                        && self.config.windows.contains_key(&desired_window)
-                    {
-                        to_update.push(((**id).clone(), widget.clone()));
-                    }
+                let desired_window = self.get_window_name(id);
+                if desired_window != *current_window {
+                    to_update.push(((**id).clone(), widget.clone(), desired_window));
                 }
             }
         }
-        for (id, widget) in to_update {
-            self.remove_activity_from_ord(&id, widget.clone()).unwrap();
-            let window_name = self.get_window_name(&id);
-            let order_managers = self.order_managers.borrow();
-            let ord = order_managers.get(&window_name).unwrap();
-            ord.borrow_mut().add(&id, widget);
+        for (id, widget, window_name) in to_update {
+            self.move_activity(&id, widget, &window_name);
             log::trace!("moving widget {} to correct window", id);
         }
         log::debug!("updated windows");
     }
 
-    fn create_new_window(&mut self, window_name: &str) {
+    fn create_new_window(&self, window_name: &str) {
         if self.order_managers.borrow().contains_key(window_name) {
             return;
         }
-        if !self.config.windows.contains_key(window_name) {
-            return;
-        }
+        let instance = match self.window_instances.borrow().get(window_name) {
+            Some(instance) => instance.clone(),
+            None => return,
+        };
         let window = gtk::ApplicationWindow::new(&self.app);
         window.set_title(Some(window_name));
         let container = gtk::Box::new(gtk::Orientation::Horizontal, 5);
         container.add_css_class("activity-container");
-        let send_cycle = self.cycle_channel.0.clone();
+        let send_cycle = self.cycle_channel.clone();
         let cycle_gesture = gtk::GestureClick::new();
         cycle_gesture.set_button(0);
         let window_name1 = window_name.to_string();
@@ -613,37 +1420,149 @@ impl DynamicLayout {
             }
         });
         container.add_controller(cycle_gesture);
+        self.add_cycle_gestures(&container, window_name);
+        // expand the window when the pointer enters and collapse it again when it's idle
+        let hover_controller = gtk::EventControllerMotion::new();
+        let this = self.clone();
+        let window_name1 = window_name.to_string();
+        hover_controller.connect_enter(move |_, _, _| {
+            this.reset_idle_collapse(&window_name1);
+        });
+        let this = self.clone();
+        let window_name1 = window_name.to_string();
+        hover_controller.connect_leave(move |_| {
+            this.reset_idle_collapse(&window_name1);
+        });
+        container.add_controller(hover_controller);
         let window = window.upcast();
+        self.watch_exclusive_zone(&window, window_name);
         if !self.order_managers.borrow().contains_key(window_name) {
             self.order_managers.borrow_mut().insert(
                 window_name.to_string(),
-                Rc::new(RefCell::new(CycleOrder::new(
-                    &self.config.get_for_window(window_name),
-                    &window,
-                    &container,
-                ))),
+                new_order_manager(&self.config_for_window(window_name), &window, &container),
             );
         }
+        if let Some(classes) = self.closed_css_classes.borrow_mut().remove(window_name) {
+            self.order_managers.borrow()[window_name]
+                .borrow_mut()
+                .set_css_classes(classes.iter().map(|c| c.as_str()).collect());
+        }
         self.configure_container(&window_name);
         window.set_child(Some(&container));
-        self.config
-            .get_for_window(&window_name)
+        self.add_keyboard_controller(&window, window_name);
+        self.add_drop_target(&container, window_name);
+        self.config_for_window(&window_name)
             .window_position
-            .init_window(&window.clone().upcast());
-        if !window_name.is_empty() {
-            window.add_css_class(window_name);
+            .init_window(&window.clone().upcast(), instance.monitor.as_ref());
+        if !instance.config_name.is_empty() {
+            window.add_css_class(&instance.config_name);
         } else {
             window.add_css_class("default-window");
         }
+        self.reset_idle_collapse(window_name);
         //show window
-        window.present();
+        if instance.hidden {
+            instance.update_visibility(&window);
+        } else {
+            window.present();
+        }
+    }
+
+    /// Closes the window of `ord` keeping its css classes for when it's created again
+    fn close_window(&self, window_name: &str, ord: &dyn WidgetOrderManager) {
+        let classes = ord.list_css_classes();
+        if !classes.is_empty() {
+            self.closed_css_classes
+                .borrow_mut()
+                .insert(window_name.to_string(), classes);
+        }
+        ord.get_window().close();
+    }
+
+    /// Replaces the order manager of `window_name` if the configured strategy changed,
+    /// the activities and their active state are moved to the new one
+    fn update_order_strategy(&self, window_name: &str) {
+        let config = self.config_for_window(window_name);
+        let old_ord = match self.order_managers.borrow().get(window_name) {
+            Some(ord) => ord.clone(),
+            None => return,
+        };
+        if old_ord.borrow().strategy() == config.order_strategy {
+            return;
+        }
+        close_window_popup(&mut *old_ord.borrow_mut());
+        let old_ord = old_ord.borrow();
+        let new_ord = new_order_manager(&config, &old_ord.get_window(), &old_ord.get_container());
+        {
+            let mut new = new_ord.borrow_mut();
+            let activity_container = new.activity_container_mut();
+            activity_container.grid_columns = old_ord.activity_container().grid_columns;
+            activity_container.orientation = old_ord.activity_container().orientation;
+            let widget_map = old_ord.get_widget_map();
+            let activities = old_ord.list_activities();
+            for id in activities.iter() {
+                let widget = widget_map.borrow().get(id).unwrap().clone();
+                new.add(id, widget);
+            }
+            for id in activities.iter().filter(|id| old_ord.is_active(id)) {
+                new.activate(id);
+            }
+            new.set_css_classes(
+                old_ord
+                    .list_css_classes()
+                    .iter()
+                    .map(|c| c.as_str())
+                    .collect(),
+            );
+            if old_ord.is_collapsed() {
+                set_collapsed(&mut *new, true);
+            }
+        }
+        let refresh_after = new_ord.borrow().refresh_after();
+        schedule_refresh(&new_ord, refresh_after);
+        log::debug!(
+            "changing order strategy of window {} to {:?}",
+            window_name,
+            config.order_strategy
+        );
+        self.order_managers
+            .borrow_mut()
+            .insert(window_name.to_string(), new_ord);
+    }
+
+    /// Moves `activity` from its current window to `window_name`
+    pub(crate) fn move_activity(
+        &self,
+        activity: &ActivityIdentifier,
+        widget: ActivityWidget,
+        window_name: &str,
+    ) {
+        self.remove_activity_from_ord(activity, widget.clone())
+            .unwrap();
+        let ord = self
+            .order_managers
+            .borrow()
+            .get(window_name)
+            .unwrap()
+            .clone();
+        ord.borrow_mut().add(activity, widget);
+        let config = self.config_for_window(window_name);
+        if config.reorder_on_add {
+            Self::update_activity_order(&ord, &config);
+        }
+        self.persistent_state
+            .borrow()
+            .restore_order(window_name, &mut *ord.borrow_mut());
+        self.save_order(&ord);
+        self.configure_widget(activity);
     }
 
     fn remove_activity_from_ord(
-        &mut self,
+        &self,
         activity: &ActivityIdentifier,
         widget: ActivityWidget,
     ) -> Result<()> {
+        self.close_popup(activity);
         let widget_container = match widget.parent().unwrap().downcast::<gtk::Box>() {
             Ok(parent) => parent,
             Err(_) => {
@@ -665,7 +1584,7 @@ impl DynamicLayout {
                 if container.first_child().is_some() {
                     return Ok(());
                 }
-                ord.borrow().get_window().close();
+                self.close_window(name, &*ord.borrow());
             } else {
                 continue;
             };
@@ -678,7 +1597,10 @@ impl DynamicLayout {
         Ok(())
     }
 
-    fn update_activity_order(order: &Rc<RefCell<CycleOrder>>, config: &DynamicLayoutConfig) {
+    fn update_activity_order(
+        order: &Rc<RefCell<dyn WidgetOrderManager>>,
+        config: &DynamicLayoutConfig,
+    ) {
         let activities = order.borrow().list_activities();
         let mut buckets: Vec<(ActivityMatch, Vec<&ActivityIdentifier>)> = config
             .activity_order
@@ -706,3 +1628,57 @@ impl DynamicLayout {
         order.borrow_mut().update_order(final_order);
     }
 }
+
+fn format_problems(problems: &[String]) -> String {
+    let mut message = format!("The config has {} problems:", problems.len());
+    for problem in problems {
+        message.push_str("\n    ");
+        message.push_str(problem);
+    }
+    message
+}
+
+/// Returns nothing if there are no warnings, otherwise a line for each one
+fn format_warnings(warnings: &[String]) -> String {
+    if warnings.is_empty() {
+        return String::new();
+    }
+    let mut message = format!("\n{} warnings:", warnings.len());
+    for warning in warnings {
+        message.push_str("\n    ");
+        message.push_str(warning);
+    }
+    message
+}
+
+/// Explains why activating `id` changed nothing
+fn activation_error(ord: &dyn WidgetOrderManager, id: &ActivityIdentifier) -> RBoxError {
+    if ord.is_active(id) {
+        RBoxError::from_fmt("Activity is already active")
+    } else if !ord.is_shown(id) && ord.strategy() == OrderStrategy::Fixed {
+        RBoxError::from_fmt(
+            "Activity is hidden, the fixed order strategy only activates the shown activities",
+        )
+    } else {
+        RBoxError::from_fmt("No activity can be active in this window, max_active is 0")
+    }
+}
+
+fn parse_activity_mode(mode: &str) -> Option<ActivityMode> {
+    match mode.to_lowercase().as_str() {
+        "minimal" => Some(ActivityMode::Minimal),
+        "compact" => Some(ActivityMode::Compact),
+        "expanded" => Some(ActivityMode::Expanded),
+        "overlay" => Some(ActivityMode::Overlay),
+        _ => None,
+    }
+}
+
+pub(crate) fn activity_mode_name(mode: ActivityMode) -> &'static str {
+    match mode {
+        ActivityMode::Minimal => "minimal",
+        ActivityMode::Compact => "compact",
+        ActivityMode::Expanded => "expanded",
+        ActivityMode::Overlay => "overlay",
+    }
+}
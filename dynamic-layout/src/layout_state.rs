@@ -0,0 +1,82 @@
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+use dynisland_core::abi::gtk;
+use gtk::prelude::*;
+use serde::Serialize;
+
+use crate::{
+    layout::activity_mode_name, persistent_state::activity_key, priority_order::WidgetOrderManager,
+    window_position::window_connector,
+};
+
+/// Snapshot of the layout, returned by the `state` command
+#[derive(Debug, Serialize)]
+pub struct LayoutState {
+    pub(crate) windows: Vec<WindowState>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct WindowState {
+    pub(crate) name: String,
+    /// connector of the monitor the window is on, if it's known
+    pub(crate) monitor: Option<String>,
+    pub(crate) visible: bool,
+    pub(crate) css_classes: Vec<String>,
+    /// in the order they are shown
+    pub(crate) activities: Vec<ActivityState>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ActivityState {
+    pub(crate) id: String,
+    pub(crate) module: String,
+    pub(crate) activity: String,
+    pub(crate) mode: String,
+    pub(crate) is_active: bool,
+    pub(crate) is_shown: bool,
+}
+
+impl LayoutState {
+    pub fn from_order_managers(
+        order_managers: &HashMap<String, Rc<RefCell<dyn WidgetOrderManager>>>,
+    ) -> Self {
+        let mut windows: Vec<WindowState> = order_managers
+            .iter()
+            .map(|(name, ord)| WindowState::from_order_manager(name, &*ord.borrow()))
+            .collect();
+        windows.sort_by(|a, b| a.name.cmp(&b.name));
+        Self { windows }
+    }
+}
+
+impl WindowState {
+    pub fn from_order_manager(name: &str, ord: &dyn WidgetOrderManager) -> Self {
+        let window = ord.get_window();
+        let monitor = window_connector(&window);
+        let widget_map = ord.get_widget_map();
+        let widget_map = widget_map.borrow();
+        let activities = ord
+            .list_activities()
+            .iter()
+            .map(|id| ActivityState {
+                id: activity_key(id),
+                module: id.module().to_string(),
+                activity: id.activity().to_string(),
+                mode: widget_map
+                    .get(id)
+                    .map(|widget| activity_mode_name(widget.mode()))
+                    .unwrap_or_default()
+                    .to_string(),
+                is_active: ord.is_active(id),
+                is_shown: ord.is_shown(id),
+            })
+            .collect();
+        Self {
+            name: name.to_string(),
+            monitor,
+            visible: window.is_visible(),
+            css_classes: ord.list_css_classes(),
+            activities,
+        }
+    }
+}
@@ -5,9 +5,19 @@ use dynisland_core::abi::{
     abi_stable,
     layout::{LayoutManagerBuilder, LayoutManagerBuilderRef},
 };
+mod activity_match;
 mod config;
+mod drag_reorder;
+mod fullscreen;
+mod gestures;
+mod keyboard;
 mod layout;
+mod layout_state;
+mod notification_queue;
+mod persistent_state;
+mod popup;
 mod priority_order;
+mod profile;
 mod window_position;
 use layout::new;
 
@@ -0,0 +1,269 @@
+use std::{collections::VecDeque, time::Duration};
+
+use dynisland_core::{
+    abi::{gdk, glib, gtk, module::ActivityIdentifier},
+    graphics::activity_widget::boxed_activity_mode::ActivityMode,
+};
+use glib::SourceId;
+use gtk::prelude::*;
+
+use crate::layout::DynamicLayout;
+
+/// A notification waiting to be shown
+#[derive(Debug, Clone)]
+pub struct Notification {
+    pub(crate) activity: ActivityIdentifier,
+    pub(crate) mode: ActivityMode,
+    pub(crate) duration: Duration,
+}
+
+impl Notification {
+    /// Only expanded and overlay notifications count for `max_notifications`
+    pub fn is_expanded(&self) -> bool {
+        matches!(self.mode, ActivityMode::Expanded | ActivityMode::Overlay)
+    }
+}
+
+/// Expanded notifications of a window, they are shown in the order they arrived
+#[derive(Debug, Default)]
+pub struct NotificationQueue {
+    /// activities that are showing a notification, if it is expanded and the timeout that ends it
+    pub(crate) playing: Vec<(ActivityIdentifier, bool, SourceId)>,
+    pub(crate) pending: VecDeque<Notification>,
+}
+
+impl NotificationQueue {
+    /// Removes `id` from the playing notifications and returns the timeout that would end it
+    pub fn take_playing(&mut self, id: &ActivityIdentifier) -> Option<SourceId> {
+        let idx = self
+            .playing
+            .iter()
+            .position(|(playing, _, _)| playing == id)?;
+        Some(self.playing.remove(idx).2)
+    }
+
+    /// Returns true if another expanded notification can be shown, `0` is no limit
+    pub fn can_play(&self, max_notifications: usize) -> bool {
+        max_notifications == 0 || self.expanded_playing() < max_notifications
+    }
+
+    fn expanded_playing(&self) -> usize {
+        self.playing
+            .iter()
+            .filter(|(_, expanded, _)| *expanded)
+            .count()
+    }
+
+    /// Returns true if `id` is showing an expanded notification,
+    /// `None` if it isn't showing any
+    fn playing_expanded(&self, id: &ActivityIdentifier) -> Option<bool> {
+        self.playing
+            .iter()
+            .find(|(playing, _, _)| playing == id)
+            .map(|(_, expanded, _)| *expanded)
+    }
+
+    /// Replaces the pending notification of the same activity keeping its place,
+    /// returns false if the activity had no pending notification
+    pub fn replace_pending(&mut self, notification: &Notification) -> bool {
+        match self
+            .pending
+            .iter_mut()
+            .find(|pending| pending.activity == notification.activity)
+        {
+            Some(pending) => {
+                *pending = notification.clone();
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// What happens to a new notification
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NotificationAction {
+    /// it's shown now, replacing the one the activity is showing
+    Play,
+    /// it replaces the one of the same activity that is waiting, keeping its place
+    ReplacePending,
+    /// it waits for the ones before it, the one the activity is showing ends
+    Queue,
+}
+
+/// Decides what to do with a new notification when `expanded_playing` expanded notifications
+/// are shown. `playing` is `Some(expanded)` if the activity is already showing a notification,
+/// that one is replaced so it doesn't count for `max_notifications`, `0` is no limit
+pub(crate) fn notification_action(
+    expanded_playing: usize,
+    playing: Option<bool>,
+    pending: bool,
+    expanded: bool,
+    max_notifications: usize,
+) -> NotificationAction {
+    if playing.is_none() && pending {
+        return NotificationAction::ReplacePending;
+    }
+    let expanded_playing = match playing {
+        Some(true) => expanded_playing.saturating_sub(1),
+        _ => expanded_playing,
+    };
+    if !expanded || max_notifications == 0 || expanded_playing < max_notifications {
+        NotificationAction::Play
+    } else {
+        NotificationAction::Queue
+    }
+}
+
+impl DynamicLayout {
+    /// Shows the notification now if it isn't expanded or if less than `max_notifications`
+    /// expanded ones are shown in the window, otherwise after the ones before it.
+    /// A new notification of an activity that is already queued or shown replaces the old one,
+    /// a shown one that becomes expanded ends and waits if the window is full
+    pub(crate) fn queue_notification(&self, window_name: &str, notification: Notification) {
+        let max_notifications = self.config_for_window(window_name).max_notifications as usize;
+        let (action, replaced_playing) = {
+            let mut queues = self.notification_queues.borrow_mut();
+            let queue = queues.entry(window_name.to_string()).or_default();
+            let pending = queue
+                .pending
+                .iter()
+                .any(|pending| pending.activity == notification.activity);
+            let action = notification_action(
+                queue.expanded_playing(),
+                queue.playing_expanded(&notification.activity),
+                pending,
+                notification.is_expanded(),
+                max_notifications,
+            );
+            let mut replaced_playing = false;
+            if action != NotificationAction::ReplacePending {
+                if let Some(timeout) = queue.take_playing(&notification.activity) {
+                    // it's restarted with the new mode and duration, or it waits
+                    timeout.remove();
+                    replaced_playing = true;
+                }
+            }
+            match action {
+                NotificationAction::ReplacePending => {
+                    queue.replace_pending(&notification);
+                }
+                NotificationAction::Queue => queue.pending.push_back(notification.clone()),
+                NotificationAction::Play => {}
+            }
+            (action, replaced_playing)
+        };
+        match action {
+            NotificationAction::Play => self.play_notification(window_name, notification),
+            NotificationAction::Queue if replaced_playing => {
+                self.end_notification(&notification.activity)
+            }
+            _ => {}
+        }
+    }
+
+    fn play_notification(&self, window_name: &str, notification: Notification) {
+        let (widget, activity_window) =
+            match Self::find_widget(&self.order_managers.borrow(), &notification.activity) {
+                Some(found) => found,
+                None => {
+                    // the activity was removed while waiting
+                    self.play_next_notification(window_name);
+                    return;
+                }
+            };
+        let ord = self
+            .order_managers
+            .borrow()
+            .get(&activity_window)
+            .unwrap()
+            .clone();
+        if !ord.borrow().is_shown(&notification.activity) {
+            widget.set_visible(true);
+            widget.remove_css_class("hidden");
+        }
+        widget.set_mode(notification.mode);
+        let expanded = notification.is_expanded();
+
+        let this = self.clone();
+        let window = window_name.to_string();
+        let activity = notification.activity.clone();
+        let timeout = glib::timeout_add_local_once(notification.duration, move || {
+            if let Some(queue) = this.notification_queues.borrow_mut().get_mut(&window) {
+                queue.playing.retain(|(playing, _, _)| *playing != activity);
+            }
+            this.end_notification(&activity);
+            this.play_next_notification(&window);
+        });
+        self.notification_queues
+            .borrow_mut()
+            .entry(window_name.to_string())
+            .or_default()
+            .playing
+            .push((notification.activity, expanded, timeout));
+    }
+
+    fn play_next_notification(&self, window_name: &str) {
+        let max_notifications = self.config_for_window(window_name).max_notifications as usize;
+        let next = self
+            .notification_queues
+            .borrow_mut()
+            .get_mut(window_name)
+            .filter(|queue| queue.can_play(max_notifications))
+            .and_then(|queue| queue.pending.pop_front());
+        if let Some(next) = next {
+            self.play_notification(window_name, next);
+        }
+    }
+
+    /// Puts the activity back in the mode it had before the notification
+    fn end_notification(&self, activity: &ActivityIdentifier) {
+        let (widget, window_name) = match Self::find_widget(&self.order_managers.borrow(), activity)
+        {
+            Some(found) => found,
+            None => return,
+        };
+        let ord = self
+            .order_managers
+            .borrow()
+            .get(&window_name)
+            .unwrap()
+            .clone();
+        if ord.borrow().is_active(activity) {
+            widget.set_mode(ActivityMode::Compact);
+        } else {
+            widget.set_mode(ActivityMode::Minimal);
+        }
+        if !ord.borrow().is_shown(activity) {
+            widget.add_css_class("hidden");
+            widget.size_allocate(&gdk::Rectangle::new(0, 0, 50, 40), 0);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use NotificationAction::*;
+
+    #[test]
+    fn expanded_notifications_wait_when_full() {
+        assert_eq!(notification_action(1, None, false, true, 2), Play);
+        assert_eq!(notification_action(2, None, false, true, 2), Queue);
+        assert_eq!(notification_action(5, None, false, true, 0), Play);
+        // compact and minimal ones are always shown
+        assert_eq!(notification_action(2, None, false, false, 2), Play);
+        assert_eq!(notification_action(2, None, true, true, 2), ReplacePending);
+    }
+
+    #[test]
+    fn replacing_a_playing_notification_respects_the_limit() {
+        // the replaced expanded notification frees its place
+        assert_eq!(notification_action(2, Some(true), false, true, 2), Play);
+        // a compact notification that becomes expanded needs a free place
+        assert_eq!(notification_action(2, Some(false), false, true, 2), Queue);
+        assert_eq!(notification_action(1, Some(false), false, true, 2), Play);
+        assert_eq!(notification_action(2, Some(false), false, false, 2), Play);
+        assert_eq!(notification_action(2, Some(false), false, true, 0), Play);
+    }
+}
@@ -0,0 +1,314 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    fs,
+    path::PathBuf,
+    rc::{Rc, Weak},
+    time::Duration,
+};
+
+use dynisland_core::{
+    abi::{glib, glib::SourceId, log, module::ActivityIdentifier},
+    ron,
+};
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::priority_order::WidgetOrderManager;
+
+/// How many removed activities of a window are remembered, the least recently removed ones
+/// are forgotten first
+const MAX_REMOVED_ACTIVITIES: usize = 32;
+
+/// How long the order has to stay the same before it's written, so cycling or scrolling through
+/// the activities doesn't write the file at every step
+const SAVE_ORDER_DELAY: Duration = Duration::from_millis(1000);
+
+/// State of the layout that is kept across reloads and restarts,
+/// it's saved in `$XDG_STATE_HOME/dynisland/dynamic-layout.ron`
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PersistentState {
+    /// `activity@module` -> window chosen with the `move` command
+    pub(crate) window_overrides: HashMap<String, String>,
+    /// window name -> last order of its activities
+    pub(crate) windows: HashMap<String, SavedOrder>,
+    /// window name -> `activity_order` suggested by the last drag and drop,
+    /// printed by the `export-order` command
+    pub(crate) suggestions: HashMap<String, Vec<String>>,
+    /// the state shared with the layout, used by the delayed save
+    #[serde(skip)]
+    this: Weak<RefCell<PersistentState>>,
+    /// delayed save scheduled by `save_order`
+    #[serde(skip)]
+    pending_save: Option<SourceId>,
+}
+
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SavedOrder {
+    /// `activity@module`, it also contains the activities that were removed
+    pub(crate) order: Vec<String>,
+    pub(crate) active: Vec<String>,
+    /// activities in `order` that are not in the window, least recently removed first
+    pub(crate) removed: Vec<String>,
+}
+
+impl SavedOrder {
+    /// Merges the `current` activities of the window, and the `active` ones, in the saved order,
+    /// returns false if nothing changed
+    pub fn update(&mut self, current: &[String], active: Vec<String>) -> bool {
+        let mut removed: Vec<String> = self
+            .removed
+            .iter()
+            .filter(|key| !current.contains(key))
+            .cloned()
+            .collect();
+        for key in self.order.iter() {
+            if !current.contains(key) && !removed.contains(key) {
+                removed.push(key.clone());
+            }
+        }
+        if removed.len() > MAX_REMOVED_ACTIVITIES {
+            removed.drain(..removed.len() - MAX_REMOVED_ACTIVITIES);
+        }
+
+        let mut order = current.to_vec();
+        for (i, key) in self.order.iter().enumerate() {
+            if order.contains(key) || !removed.contains(key) {
+                continue;
+            }
+            // put it after the closest activity that was before it
+            let position = self.order[..i]
+                .iter()
+                .rev()
+                .find_map(|prev| order.iter().position(|k| k == prev))
+                .map(|pos| pos + 1)
+                .unwrap_or(0);
+            order.insert(position, key.clone());
+        }
+        let mut active = active;
+        for key in self.active.iter() {
+            if removed.contains(key) && !active.contains(key) {
+                active.push(key.clone());
+            }
+        }
+        if self.order == order && self.active == active && self.removed == removed {
+            return false;
+        }
+        self.order = order;
+        self.active = active;
+        self.removed = removed;
+        true
+    }
+}
+
+impl PersistentState {
+    pub fn path() -> Option<PathBuf> {
+        let state_dir = match std::env::var_os("XDG_STATE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+        };
+        Some(state_dir.join("dynisland").join("dynamic-layout.ron"))
+    }
+
+    /// Loads the state and shares it, the shared state is needed to delay the saves
+    pub fn load_shared() -> Rc<RefCell<Self>> {
+        Rc::new_cyclic(|this| {
+            let mut state = Self::load();
+            state.this = this.clone();
+            RefCell::new(state)
+        })
+    }
+
+    pub fn load() -> Self {
+        let path = match Self::path() {
+            Some(path) => path,
+            None => return Self::default(),
+        };
+        let content = match fs::read_to_string(&path) {
+            Ok(content) => content,
+            Err(_) => return Self::default(),
+        };
+        match ron::from_str(&content) {
+            Ok(state) => state,
+            Err(err) => {
+                log::warn!("failed to parse layout state {}: {err}", path.display());
+                Self::default()
+            }
+        }
+    }
+
+    /// Writes the state now, a delayed save is no longer needed
+    pub fn save(&mut self) {
+        if let Some(source) = self.pending_save.take() {
+            source.remove();
+        }
+        let path = match Self::path() {
+            Some(path) => path,
+            None => {
+                log::warn!("failed to save layout state: no state directory");
+                return;
+            }
+        };
+        let content = match ron::ser::to_string_pretty(self, PrettyConfig::default()) {
+            Ok(content) => content,
+            Err(err) => {
+                log::warn!("failed to serialize layout state: {err}");
+                return;
+            }
+        };
+        if let Some(parent) = path.parent() {
+            if let Err(err) = fs::create_dir_all(parent) {
+                log::warn!("failed to create {}: {err}", parent.display());
+                return;
+            }
+        }
+        if let Err(err) = fs::write(&path, content) {
+            log::warn!("failed to save layout state to {}: {err}", path.display());
+        }
+    }
+
+    /// Writes the state if a delayed save is still waiting, used before the application exits
+    pub fn flush(&mut self) {
+        if self.pending_save.is_some() {
+            self.save();
+        }
+    }
+
+    /// Writes the state after `SAVE_ORDER_DELAY`, every call starts the delay again
+    fn save_later(&mut self) {
+        if let Some(source) = self.pending_save.take() {
+            source.remove();
+        }
+        let this = self.this.clone();
+        if this.upgrade().is_none() {
+            // not shared, there is no state to save later
+            self.save();
+            return;
+        }
+        self.pending_save = Some(glib::timeout_add_local_once(SAVE_ORDER_DELAY, move || {
+            if let Some(state) = this.upgrade() {
+                let mut state = state.borrow_mut();
+                // the source is removed when this returns
+                state.pending_save = None;
+                state.save();
+            }
+        }));
+    }
+
+    /// Saves the order of `window_name`, the saved activities that are not in the window anymore
+    /// keep their relative position, up to `MAX_REMOVED_ACTIVITIES` of them.
+    /// The file is written after `SAVE_ORDER_DELAY` without changes
+    pub fn save_order(&mut self, window_name: &str, ord: &dyn WidgetOrderManager) {
+        let activities = ord.list_activities();
+        let current: Vec<String> = activities.iter().map(|id| activity_key(id)).collect();
+        let active: Vec<String> = activities
+            .iter()
+            .filter(|id| ord.is_active(id))
+            .map(|id| activity_key(id))
+            .collect();
+        let saved = self.windows.entry(window_name.to_string()).or_default();
+        if saved.update(&current, active) {
+            self.save_later();
+        }
+    }
+
+    /// Saves the current order of `window_name` as its `activity_order` suggestion
+    pub fn save_suggestion(&mut self, window_name: &str, ord: &dyn WidgetOrderManager) {
+        let order: Vec<String> = ord
+            .list_activities()
+            .iter()
+            .map(|id| activity_key(id))
+            .collect();
+        if self.suggestions.get(window_name) == Some(&order) {
+            return;
+        }
+        self.suggestions.insert(window_name.to_string(), order);
+        self.save();
+    }
+
+    /// Reorders the saved activities of `window_name` and activates the ones that were active,
+    /// the activities that were never saved keep their position
+    pub fn restore_order(&self, window_name: &str, ord: &mut dyn WidgetOrderManager) {
+        let saved = match self.windows.get(window_name) {
+            Some(saved) => saved,
+            None => return,
+        };
+        let current = ord.list_activities();
+        let saved_idx = |id: &Rc<ActivityIdentifier>| {
+            let key = activity_key(id);
+            saved.order.iter().position(|k| *k == key)
+        };
+        let known_positions: Vec<usize> = current
+            .iter()
+            .enumerate()
+            .filter(|(_, id)| saved_idx(*id).is_some())
+            .map(|(pos, _)| pos)
+            .collect();
+        let mut known: Vec<&Rc<ActivityIdentifier>> = current
+            .iter()
+            .filter(|id| saved_idx(*id).is_some())
+            .collect();
+        known.sort_by_key(|id| saved_idx(*id));
+
+        let mut order: Vec<&ActivityIdentifier> = current.iter().map(|id| id.as_ref()).collect();
+        for (pos, id) in known_positions.into_iter().zip(known) {
+            order[pos] = id;
+        }
+        ord.update_order(order);
+
+        let to_activate: Vec<Rc<ActivityIdentifier>> = ord
+            .list_activities()
+            .into_iter()
+            .filter(|id| saved.active.contains(&activity_key(id)) && !ord.is_active(id))
+            .collect();
+        for id in to_activate {
+            ord.activate(&id);
+        }
+    }
+}
+
+pub(crate) fn activity_key(id: &ActivityIdentifier) -> String {
+    format!("{}@{}", id.activity(), id.module())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keys(keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn removed_activities_keep_their_position() {
+        let mut saved = SavedOrder::default();
+        assert!(saved.update(&keys(&["a", "b", "c"]), keys(&["b"])));
+        assert!(saved.update(&keys(&["a", "c"]), keys(&[])));
+        assert_eq!(saved.order, keys(&["a", "b", "c"]));
+        assert_eq!(saved.active, keys(&["b"]));
+        assert_eq!(saved.removed, keys(&["b"]));
+        assert!(!saved.update(&keys(&["a", "c"]), keys(&[])));
+        // it's added back
+        assert!(saved.update(&keys(&["a", "c", "b"]), keys(&["b"])));
+        assert!(saved.removed.is_empty());
+    }
+
+    #[test]
+    fn removed_activities_are_capped() {
+        let mut saved = SavedOrder::default();
+        let all: Vec<String> = (0..MAX_REMOVED_ACTIVITIES + 10)
+            .map(|i| format!("activity-{i}@Module"))
+            .collect();
+        for key in all.iter() {
+            saved.update(std::slice::from_ref(key), Vec::new());
+        }
+        saved.update(&[], Vec::new());
+        assert_eq!(saved.removed.len(), MAX_REMOVED_ACTIVITIES);
+        assert_eq!(saved.order.len(), MAX_REMOVED_ACTIVITIES);
+        // the first ones were removed first
+        assert!(!saved.order.contains(&all[0]));
+        assert!(saved.order.contains(all.last().unwrap()));
+    }
+}
@@ -0,0 +1,157 @@
+use std::rc::Rc;
+
+use dynisland_core::{
+    abi::{gtk, log, module::ActivityIdentifier},
+    graphics::activity_widget::{boxed_activity_mode::ActivityMode, ActivityWidget},
+};
+use gtk::prelude::*;
+
+use crate::{
+    config::schedule_minimize,
+    layout::DynamicLayout,
+    priority_order::{update_ui, WidgetOrderManager},
+};
+
+/// An activity in expanded or overlay mode shown in its own window,
+/// a placeholder keeps its place in the container
+#[derive(Debug)]
+pub struct ActivityPopup {
+    pub(crate) activity: Rc<ActivityIdentifier>,
+    pub(crate) window: gtk::Window,
+    pub(crate) placeholder: gtk::Box,
+}
+
+impl DynamicLayout {
+    /// Moves the activity in or out of the popup window when its mode changes
+    pub(crate) fn update_popup(&self, activity: &ActivityIdentifier) {
+        let (widget, window_name) = match Self::find_widget(&self.order_managers.borrow(), activity)
+        {
+            Some(found) => found,
+            None => return,
+        };
+        let expanded = matches!(
+            widget.mode(),
+            ActivityMode::Expanded | ActivityMode::Overlay
+        );
+        let expanded_popup = self.config_for_window(&window_name).expanded_popup;
+        if expanded && expanded_popup {
+            self.open_popup(activity, &widget, &window_name);
+        } else {
+            self.close_popup(activity);
+        }
+    }
+
+    fn open_popup(
+        &self,
+        activity: &ActivityIdentifier,
+        widget: &ActivityWidget,
+        window_name: &str,
+    ) {
+        let ord = self
+            .order_managers
+            .borrow()
+            .get(window_name)
+            .unwrap()
+            .clone();
+        let previous = match &ord.borrow().activity_container().popup {
+            Some(popup) if popup.activity.as_ref() == activity => return,
+            Some(popup) => Some(popup.activity.clone()),
+            None => None,
+        };
+        if let Some(previous) = previous {
+            // only one activity of the window is in the popup
+            self.close_popup(&previous);
+            let found = Self::find_widget(&self.order_managers.borrow(), &previous);
+            if let Some((widget, _)) = found {
+                widget.set_mode(ActivityMode::Compact);
+            }
+        }
+        let container = ord.borrow().get_container();
+        if widget.parent().as_ref() != Some(container.upcast_ref::<gtk::Widget>()) {
+            return;
+        }
+
+        let config = self.config_for_window(window_name);
+        let island = ord.borrow().get_window();
+        let window = gtk::Window::new();
+        if !config.window_position.init_popup(&window, &island) {
+            // the activity is expanded in its container instead
+            window.destroy();
+            log::debug!("no space for the popup of {window_name}");
+            return;
+        }
+        window.set_application(Some(&self.app));
+        window.set_title(Some(&format!("{window_name} popup")));
+        window.add_css_class("popup-window");
+        for class in island.css_classes() {
+            window.add_css_class(&class);
+        }
+        let timeout = config.auto_minimize_timeout;
+        if timeout >= 0 {
+            let cancel_minimize = self.cancel_minimize.clone();
+            let activity = activity.clone();
+            let widget = widget.clone();
+            window.connect_is_active_notify(move |window| {
+                // the focus went to another window, like after clicking outside the popup
+                if !window.is_active() {
+                    schedule_minimize(&cancel_minimize, &activity, widget.clone(), timeout);
+                }
+            });
+        }
+
+        // keeps the size the widget had before it was expanded
+        let placeholder = gtk::Box::new(gtk::Orientation::Horizontal, 0);
+        placeholder.add_css_class("popup-placeholder");
+        placeholder.set_size_request(widget.width(), widget.height());
+        container.insert_child_after(&placeholder, widget.prev_sibling().as_ref());
+        container.remove(widget);
+
+        window.set_child(Some(widget));
+        window.present();
+        log::debug!("showing {activity} in a popup");
+
+        let mut ord = ord.borrow_mut();
+        ord.activity_container_mut().popup = Some(ActivityPopup {
+            activity: Rc::new(activity.clone()),
+            window,
+            placeholder,
+        });
+        update_ui(&*ord);
+    }
+
+    /// Puts the activity back in its container if it's in the popup
+    pub(crate) fn close_popup(&self, activity: &ActivityIdentifier) {
+        let ord = self.order_managers.borrow().values().find_map(|ord| {
+            ord.borrow()
+                .activity_container()
+                .popup
+                .as_ref()
+                .is_some_and(|popup| popup.activity.as_ref() == activity)
+                .then(|| ord.clone())
+        });
+        if let Some(ord) = ord {
+            close_window_popup(&mut *ord.borrow_mut());
+        }
+    }
+}
+
+/// Puts the activity in the popup of the window back in its container
+pub(crate) fn close_window_popup(ord: &mut dyn WidgetOrderManager) {
+    let popup = match ord.activity_container_mut().popup.take() {
+        Some(popup) => popup,
+        None => return,
+    };
+    let widget = popup
+        .window
+        .child()
+        .and_then(|child| child.downcast::<ActivityWidget>().ok());
+    popup.window.set_child(None::<&gtk::Widget>);
+    popup.window.destroy();
+    let container = ord.get_container();
+    if let Some(widget) = widget {
+        container.insert_child_after(&widget, Some(&popup.placeholder));
+    }
+    container.remove(&popup.placeholder);
+    log::debug!("{} is back in its window", popup.activity);
+    update_ui(ord);
+}
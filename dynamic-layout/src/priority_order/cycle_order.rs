@@ -1,312 +1,135 @@
-use std::{
-    cell::RefCell,
-    collections::{HashMap, HashSet, VecDeque},
-    rc::Rc,
-};
+use std::rc::Rc;
 
 use dynisland_core::{
-    abi::{gdk, glib, gtk, module::ActivityIdentifier},
-    graphics::activity_widget::{boxed_activity_mode::ActivityMode, ActivityWidget},
+    abi::{gtk, module::ActivityIdentifier},
+    graphics::activity_widget::ActivityWidget,
 };
-use gdk::prelude::ListModelExtManual;
-use gtk::prelude::*;
 
+use super::{
+    cycle_state::CycleState, same_activities, update_ui, ActivityContainer, OrderStrategy,
+    WidgetOrderManager,
+};
 use crate::config::DynamicLayoutConfig;
 
 #[derive(Debug)]
 pub struct CycleOrder {
-    pub(crate) window: gtk::Window,
-    pub(crate) container: gtk::Box,
-    pub(crate) widget_map: Rc<RefCell<HashMap<Rc<ActivityIdentifier>, ActivityWidget>>>,
-    pub(crate) order: VecDeque<Rc<ActivityIdentifier>>,
-    pub(crate) active: u16,
-    pub(crate) active_offset: u16,
-    pub(crate) max_shown: u16,
-    pub(crate) max_active: u16,
-    pub(crate) css_classes: Vec<String>,
+    pub(crate) ui: ActivityContainer,
+    pub(crate) state: CycleState<Rc<ActivityIdentifier>>,
 }
 impl CycleOrder {
     pub fn new(config: &DynamicLayoutConfig, window: &gtk::Window, container: &gtk::Box) -> Self {
-        let max_active = config.max_active.min(config.max_activities);
         CycleOrder {
-            max_active,
-            max_shown: config.max_activities,
-            container: container.clone(),
-            window: window.clone(),
-            widget_map: Rc::new(RefCell::new(HashMap::new())),
-            order: VecDeque::new(),
-            active: 0,
-            active_offset: 0,
-            css_classes: Vec::new(),
-        }
-    }
-    fn update_ui(&self) {
-        //remove widgets
-        if self.container.clone().first_child().is_none() && self.widget_map.borrow().is_empty() {
-            return;
-        }
-
-        let all_widgets = self
-            .widget_map
-            .borrow()
-            .values()
-            .cloned()
-            .collect::<HashSet<ActivityWidget>>();
-        let mut to_remove = Vec::new();
-        let mut container_children = HashSet::new();
-        for widget in self
-            .container
-            .clone()
-            .observe_children()
-            .iter::<glib::Object>()
-            .flatten()
-        {
-            let widget = widget.downcast::<ActivityWidget>().unwrap();
-            container_children.insert(widget.clone());
-            if !all_widgets.contains(&widget) {
-                to_remove.push(widget);
-            }
-        }
-        for widget in to_remove {
-            self.container.clone().remove(&widget);
-        }
-
-        //add widgets
-        for widget in all_widgets {
-            if !container_children.contains(&widget) {
-                self.container.clone().append(&widget);
-            }
-        }
-
-        //reorder and activate/deactivate widgets
-        let mut last_widget = None::<&ActivityWidget>;
-        let widget_map = self.widget_map.borrow();
-        for widget_id in self.order.iter() {
-            let widget = widget_map.get(widget_id.as_ref()).unwrap();
-            self.container
-                .clone()
-                .reorder_child_after(widget, last_widget);
-
-            if self.is_active(widget_id) {
-                widget.set_mode(ActivityMode::Compact);
-            } else {
-                widget.set_mode(ActivityMode::Minimal);
-            }
-            if self.is_shown(widget_id) {
-                widget.set_visible(true);
-                widget.remove_css_class("hidden");
-            } else {
-                widget.add_css_class("hidden");
-            }
-            last_widget = Some(widget);
+            ui: ActivityContainer::new(window, container),
+            state: CycleState::new(config.max_active, config.max_activities),
         }
     }
 }
 
-impl CycleOrder {
-    pub fn is_active(&self, id: &ActivityIdentifier) -> bool {
-        if let Some(pos) = self.order.iter().position(|t| t.as_ref() == id) {
-            (pos as u16) >= self.active_offset && (pos as u16) < self.active + self.active_offset
-        } else {
-            false
-        }
+impl WidgetOrderManager for CycleOrder {
+    fn strategy(&self) -> OrderStrategy {
+        OrderStrategy::Cycle
     }
 
-    pub fn is_shown(&self, id: &ActivityIdentifier) -> bool {
-        if let Some(pos) = self.order.iter().position(|t| t.as_ref() == id) {
-            (pos as u16) < self.max_shown
-        } else {
-            false
-        }
+    fn activity_container(&self) -> &ActivityContainer {
+        &self.ui
     }
 
-    pub fn get_container(&self) -> gtk::Box {
-        self.container.clone()
+    fn activity_container_mut(&mut self) -> &mut ActivityContainer {
+        &mut self.ui
     }
 
-    pub fn get_widget_map(&self) -> Rc<RefCell<HashMap<Rc<ActivityIdentifier>, ActivityWidget>>> {
-        self.widget_map.clone()
+    fn is_active(&self, id: &ActivityIdentifier) -> bool {
+        self.state.is_active(id)
     }
-    pub fn get_window(&self) -> gtk::Window {
-        self.window.clone()
+
+    fn is_shown(&self, id: &ActivityIdentifier) -> bool {
+        self.state.is_shown(id)
     }
 
-    pub fn list_activities(&self) -> Vec<Rc<ActivityIdentifier>> {
-        self.order.iter().cloned().collect()
+    fn list_activities(&self) -> Vec<Rc<ActivityIdentifier>> {
+        self.state.order.iter().cloned().collect()
     }
 
-    pub fn update_order(&mut self, order: Vec<&ActivityIdentifier>) {
-        if self.order.len() != order.len() {
+    fn update_order(&mut self, order: Vec<&ActivityIdentifier>) {
+        if !same_activities(self.state.order.iter(), &order) {
             return;
         }
-        let mut self_ordered = self
-            .order
-            .iter()
-            .map(|s| s.as_ref())
-            .collect::<Vec<&ActivityIdentifier>>();
-        self_ordered.sort();
-        let mut other_ordered = order.clone();
-        other_ordered.sort();
-        for (i, widget) in self_ordered.iter().enumerate() {
-            if widget != &other_ordered[i] {
-                return;
-            }
-        }
-
-        self.order.clear();
-        for id in order {
-            self.order.push_back(Rc::new(id.clone()));
-        }
-        self.update_ui();
+        self.state
+            .set_order(order.into_iter().map(|id| Rc::new(id.clone())));
+        update_ui(self);
     }
 
-    pub fn update_config(&mut self, max_active: u16, max_shown: u16) {
-        let max_shown = max_shown.max(max_active);
-        self.max_active = max_active;
-        self.max_shown = max_shown;
-        self.active = self.active.min(max_active);
-
-        self.update_ui();
-    }
-
-    pub fn add(&mut self, id: &ActivityIdentifier, widget: ActivityWidget) {
-        if self.widget_map.borrow().contains_key(id) {
+    fn set_order_and_active(
+        &mut self,
+        order: Vec<&ActivityIdentifier>,
+        active: &[Rc<ActivityIdentifier>],
+    ) {
+        if !same_activities(self.state.order.iter(), &order) {
             return;
         }
-        self.widget_map
-            .borrow_mut()
-            .insert(Rc::new(id.clone()), widget);
-        let shared_id = Rc::new(id.clone());
-        self.order.push_back(shared_id.clone());
-        self.update_ui();
+        // moving the active activities would undo the drop
+        self.state
+            .set_order_and_active(order.into_iter().map(|id| Rc::new(id.clone())), |id| {
+                active.contains(id)
+            });
+        update_ui(self);
     }
 
-    pub fn remove(&mut self, id: &ActivityIdentifier) {
-        let postion = self.order.iter().position(|tid| tid.as_ref() == id);
-        if postion.is_none() {
-            return;
-        }
-        if self.is_active(id) {
-            self.active -= 1;
-        } else if postion.unwrap() < self.active_offset as usize {
-            self.active_offset -= 1;
-        }
-        self.widget_map.borrow_mut().remove(id);
-        self.order.remove(postion.unwrap());
-        self.update_ui();
+    fn update_config(&mut self, max_active: u16, max_shown: u16) {
+        self.state.set_limits(max_active, max_shown);
+        update_ui(self);
     }
 
-    pub fn activate(&mut self, id: &ActivityIdentifier) {
-        if self.is_active(id) {
+    fn add(&mut self, id: &ActivityIdentifier, widget: ActivityWidget) {
+        if self.ui.widget_map.borrow().contains_key(id) {
             return;
         }
-        let idx = match self.order.iter().position(|tid| tid.as_ref() == id) {
-            Some(idx) => idx,
-            None => {
-                return;
-            }
-        };
-        if !self.is_shown(id) {
-            let act = self.order.remove(idx).unwrap();
-            self.order
-                .insert((self.max_shown as usize).min(self.order.len()) - 1, act);
-        }
-        if self.active > 0 {
-            let act = self.order.remove(idx).unwrap();
-            if idx <= self.active_offset.into() {
-                // it's left of the activated ones
-                self.active_offset -= 1;
-                self.order.insert(self.active_offset.into(), act);
-            } else {
-                // it's right of the activated ones
-                self.order
-                    .insert((self.active_offset + self.active).into(), act);
-            }
-        } else {
-            self.active_offset = idx as u16;
-        }
-        // check if there is still space for active
-        if self.active < self.max_active {
-            self.active += 1;
-        } else if idx > self.active_offset.into() {
-            //right
-            self.active_offset += 1;
-        }
-        self.update_ui();
+        let shared_id = Rc::new(id.clone());
+        self.ui
+            .widget_map
+            .borrow_mut()
+            .insert(shared_id.clone(), widget);
+        self.state.push(shared_id);
+        update_ui(self);
     }
 
-    pub fn deactivate(&mut self, id: &ActivityIdentifier) {
-        if !self.is_active(id) {
+    fn remove(&mut self, id: &ActivityIdentifier) {
+        if self.state.remove(id).is_none() {
             return;
         }
-        let idx = match self.order.iter().position(|tid| tid.as_ref() == id) {
-            Some(idx) => idx,
-            None => {
-                return;
-            }
-        };
-
-        let dist_to_left = idx - self.active_offset as usize;
-        let dist_to_right = self.active as usize - (dist_to_left) - 1;
-        if dist_to_left < dist_to_right {
-            //move to the left
-            let act = self.order.remove(idx).unwrap();
-            self.order.insert((self.active_offset).into(), act);
-            self.active_offset += 1;
-        } else {
-            //move to the right
-            let act = self.order.remove(idx).unwrap();
-            self.order
-                .insert((self.active_offset + self.active - 1).into(), act);
-        }
-        self.active -= 1;
-        self.update_ui();
+        self.ui.widget_map.borrow_mut().remove(id);
+        update_ui(self);
     }
 
-    pub fn next(&mut self) {
-        if let Some(back) = self.order.pop_back() {
-            self.order.push_front(back);
-            self.update_ui();
+    fn activate(&mut self, id: &ActivityIdentifier) -> bool {
+        if !self.state.activate(id) {
+            return false;
         }
+        update_ui(self);
+        true
     }
 
-    pub fn previous(&mut self) {
-        if let Some(front) = self.order.pop_front() {
-            self.order.push_back(front);
-            self.update_ui();
+    fn deactivate(&mut self, id: &ActivityIdentifier) -> bool {
+        if !self.state.deactivate(id) {
+            return false;
         }
+        update_ui(self);
+        true
     }
 
-    pub fn add_css_class(&mut self, class: &str) -> bool {
-        if self.container.has_css_class(class) {
+    fn next(&mut self) -> bool {
+        if !self.state.rotate_next() {
             return false;
         }
-        self.container.add_css_class(class);
-        self.css_classes.push(class.to_string());
+        update_ui(self);
         true
     }
 
-    pub fn remove_css_class(&mut self, class: &str) -> bool {
-        if !self.css_classes.contains(&class.to_string()) {
+    fn previous(&mut self) -> bool {
+        if !self.state.rotate_previous() {
             return false;
         }
-        self.container.remove_css_class(class);
-        self.css_classes.retain(|c| c != class);
+        update_ui(self);
         true
     }
-    #[allow(dead_code)]
-    pub fn list_css_classes(&self) -> Vec<String> {
-        self.css_classes.clone()
-    }
-    #[allow(dead_code)]
-    pub fn set_css_classes(&mut self, classes: Vec<&str>) {
-        for class in self.css_classes.clone() {
-            self.container.remove_css_class(&class);
-        }
-        self.css_classes.clear();
-        for class in classes {
-            self.container.add_css_class(class);
-            self.css_classes.push(class.to_string());
-        }
-    }
 }
@@ -0,0 +1,495 @@
+use std::{borrow::Borrow, collections::VecDeque};
+
+/// Order of a [`CycleOrder`](super::cycle_order::CycleOrder) without the widgets.
+///
+/// The first `max_shown` items are shown and the `active` items starting at `active_offset`
+/// are active, the active ones are always shown.
+#[derive(Debug, Clone)]
+pub struct CycleState<T> {
+    pub(crate) order: VecDeque<T>,
+    pub(crate) active: u16,
+    pub(crate) active_offset: u16,
+    pub(crate) max_shown: u16,
+    pub(crate) max_active: u16,
+}
+
+impl<T> CycleState<T> {
+    pub fn new(max_active: u16, max_shown: u16) -> Self {
+        CycleState {
+            order: VecDeque::new(),
+            active: 0,
+            active_offset: 0,
+            max_shown,
+            max_active: max_active.min(max_shown),
+        }
+    }
+
+    pub fn position<Q>(&self, id: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        self.order.iter().position(|t| t.borrow() == id)
+    }
+
+    pub fn is_active<Q>(&self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        match self.position(id) {
+            Some(pos) => self.is_active_at(pos),
+            None => false,
+        }
+    }
+
+    pub fn is_shown<Q>(&self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        match self.position(id) {
+            Some(pos) => pos < self.max_shown as usize,
+            None => false,
+        }
+    }
+
+    fn is_active_at(&self, pos: usize) -> bool {
+        pos >= self.active_offset as usize
+            && pos < self.active_offset as usize + self.active as usize
+    }
+
+    /// Number of positions where the active items can be
+    fn shown_len(&self) -> usize {
+        (self.max_shown as usize).min(self.order.len())
+    }
+
+    pub fn set_limits(&mut self, max_active: u16, max_shown: u16) {
+        self.max_shown = max_shown;
+        self.max_active = max_active.min(max_shown);
+        self.active = self.active.min(self.max_active);
+        self.keep_active_shown();
+    }
+
+    /// Replaces the order, `order` must contain the same items
+    pub fn set_order(&mut self, order: impl IntoIterator<Item = T>) {
+        self.order = order.into_iter().collect();
+    }
+
+    /// Replaces the order and the active items at once, `order` must contain the same items.
+    ///
+    /// The active items are put next to each other from the first one, the inactive items
+    /// between them are moved right after them and every other item keeps its position
+    pub fn set_order_and_active(
+        &mut self,
+        order: impl IntoIterator<Item = T>,
+        is_active: impl Fn(&T) -> bool,
+    ) {
+        let order: Vec<T> = order.into_iter().collect();
+        let (first, last) = match (
+            order.iter().position(&is_active),
+            order.iter().rposition(&is_active),
+        ) {
+            (Some(first), Some(last)) => (first, last),
+            _ => {
+                self.order = order.into();
+                self.active = 0;
+                self.keep_active_shown();
+                return;
+            }
+        };
+        let mut new_order = VecDeque::with_capacity(order.len());
+        let mut active = Vec::new();
+        // the inactive items between the active ones and then the ones after them
+        let mut after = Vec::new();
+        for (idx, item) in order.into_iter().enumerate() {
+            if idx < first {
+                new_order.push_back(item);
+            } else if idx <= last && is_active(&item) {
+                active.push(item);
+            } else {
+                after.push(item);
+            }
+        }
+        self.active_offset = new_order.len() as u16;
+        self.active = (active.len() as u16).min(self.max_active);
+        new_order.extend(active);
+        new_order.extend(after);
+        self.order = new_order;
+        self.keep_active_shown();
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.order.push_back(item);
+    }
+
+    pub fn remove<Q>(&mut self, id: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        let pos = self.position(id)?;
+        if self.is_active_at(pos) {
+            self.active -= 1;
+        } else if pos < self.active_offset as usize {
+            self.active_offset -= 1;
+        }
+        self.order.remove(pos)
+    }
+
+    /// Activates `id` next to the other active items, if there are already `max_active`
+    /// the one on the other side is deactivated. Returns false if nothing changed
+    pub fn activate<Q>(&mut self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        let idx = match self.position(id) {
+            Some(idx) => idx,
+            None => return false,
+        };
+        if self.is_active_at(idx) || self.max_active == 0 {
+            return false;
+        }
+        let item = self.order.remove(idx).unwrap();
+        if self.active == 0 {
+            // a hidden item is moved in the last shown position
+            let idx = idx.min(self.max_shown as usize - 1);
+            self.order.insert(idx, item);
+            self.active_offset = idx as u16;
+            self.active = 1;
+            return true;
+        }
+        let left = idx < self.active_offset as usize;
+        if left {
+            self.active_offset -= 1;
+            self.order.insert(self.active_offset as usize, item);
+        } else {
+            // hidden items are put on the right too
+            self.order
+                .insert(self.active_offset as usize + self.active as usize, item);
+        }
+        if self.active < self.max_active {
+            self.active += 1;
+        } else if !left {
+            // the leftmost one is deactivated
+            self.active_offset += 1;
+        }
+        self.keep_active_shown();
+        true
+    }
+
+    /// Deactivates `id` and moves it out of the active items on the nearest side,
+    /// returns false if it wasn't active
+    pub fn deactivate<Q>(&mut self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        let idx = match self.position(id) {
+            Some(idx) if self.is_active_at(idx) => idx,
+            _ => return false,
+        };
+        let dist_to_left = idx - self.active_offset as usize;
+        let dist_to_right = self.active as usize - dist_to_left - 1;
+        let item = self.order.remove(idx).unwrap();
+        if dist_to_left < dist_to_right {
+            self.order.insert(self.active_offset as usize, item);
+            self.active_offset += 1;
+        } else {
+            self.order
+                .insert(self.active_offset as usize + self.active as usize - 1, item);
+        }
+        self.active -= 1;
+        true
+    }
+
+    /// Moves the last item to the front, returns false if there are no items
+    pub fn rotate_next(&mut self) -> bool {
+        match self.order.pop_back() {
+            Some(back) => {
+                self.order.push_front(back);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the first item to the back, returns false if there are no items
+    pub fn rotate_previous(&mut self) -> bool {
+        match self.order.pop_front() {
+            Some(front) => {
+                self.order.push_back(front);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the active items to the left, over the inactive ones, until they are all shown
+    fn keep_active_shown(&mut self) {
+        if self.active == 0 {
+            self.active_offset = self.active_offset.min(self.shown_len() as u16);
+            return;
+        }
+        while self.active_offset as usize + self.active as usize > self.shown_len()
+            && self.active_offset > 0
+        {
+            let item = self.order.remove(self.active_offset as usize - 1).unwrap();
+            self.active_offset -= 1;
+            self.order
+                .insert(self.active_offset as usize + self.active as usize, item);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn state(items: &[u32], max_active: u16, max_shown: u16) -> CycleState<u32> {
+        let mut state = CycleState::new(max_active, max_shown);
+        for item in items {
+            state.push(*item);
+        }
+        state
+    }
+
+    fn order(state: &CycleState<u32>) -> Vec<u32> {
+        state.order.iter().copied().collect()
+    }
+
+    fn active(state: &CycleState<u32>) -> Vec<u32> {
+        state
+            .order
+            .iter()
+            .copied()
+            .filter(|item| state.is_active(item))
+            .collect()
+    }
+
+    #[test]
+    fn activate_adds_to_the_active_ones() {
+        let mut state = state(&[0, 1, 2, 3], 2, 4);
+        assert!(state.activate(&1));
+        assert!(state.activate(&3));
+        assert_eq!(active(&state), vec![1, 3]);
+        assert_eq!(order(&state), vec![0, 1, 3, 2]);
+        assert!(!state.activate(&3));
+    }
+
+    #[test]
+    fn activate_deactivates_the_farthest_one() {
+        let mut state = state(&[0, 1, 2, 3], 2, 4);
+        state.activate(&1);
+        state.activate(&2);
+        state.activate(&3);
+        assert_eq!(active(&state), vec![2, 3]);
+        state.activate(&0);
+        assert_eq!(active(&state), vec![0, 2]);
+    }
+
+    #[test]
+    fn activate_hidden_moves_it_in_the_shown_ones() {
+        let mut state = state(&[0, 1, 2, 3, 4], 1, 3);
+        assert!(state.activate(&4));
+        assert!(state.is_shown(&4));
+        assert_eq!(order(&state), vec![0, 1, 4, 2, 3]);
+    }
+
+    #[test]
+    fn activate_hidden_with_active_at_the_end_keeps_them_shown() {
+        let mut state = state(&[0, 1, 2, 3], 2, 3);
+        state.activate(&1);
+        state.activate(&2);
+        assert!(state.activate(&3));
+        assert_eq!(active(&state), vec![2, 3]);
+        assert!(state.is_shown(&2) && state.is_shown(&3));
+    }
+
+    #[test]
+    fn activate_with_no_space_does_nothing() {
+        let mut state = state(&[0, 1], 1, 0);
+        assert!(!state.activate(&0));
+        assert!(active(&state).is_empty());
+    }
+
+    #[test]
+    fn deactivate_moves_to_the_nearest_side() {
+        let mut state = state(&[0, 1, 2, 3], 3, 4);
+        state.activate(&0);
+        state.activate(&1);
+        state.activate(&2);
+        assert!(state.deactivate(&0));
+        assert_eq!(active(&state), vec![1, 2]);
+        assert!(state.deactivate(&2));
+        assert_eq!(active(&state), vec![1]);
+        assert!(!state.deactivate(&3));
+    }
+
+    #[test]
+    fn remove_before_the_active_ones_keeps_them() {
+        let mut state = state(&[0, 1, 2], 1, 3);
+        state.activate(&2);
+        assert_eq!(state.remove(&0), Some(0));
+        assert_eq!(active(&state), vec![2]);
+        assert_eq!(state.remove(&2), Some(2));
+        assert!(active(&state).is_empty());
+        assert_eq!(state.remove(&5), None);
+    }
+
+    #[test]
+    fn smaller_limits_keep_the_active_ones_shown() {
+        let mut state = state(&[0, 1, 2, 3, 4], 2, 5);
+        state.activate(&3);
+        state.activate(&4);
+        state.set_limits(2, 3);
+        assert_eq!(active(&state), vec![3, 4]);
+        assert!(state.is_shown(&3) && state.is_shown(&4));
+        state.set_limits(1, 3);
+        assert_eq!(active(&state).len(), 1);
+    }
+
+    #[test]
+    fn set_order_and_active_keeps_the_dropped_position() {
+        let mut state = state(&[0, 1, 2, 3], 2, 4);
+        state.activate(&0);
+        state.activate(&1);
+        // 3 is dropped at index 1, in the middle of the active ones
+        state.set_order_and_active([0, 3, 1, 2], |item| [0, 1].contains(item));
+        assert_eq!(order(&state), vec![0, 1, 3, 2]);
+        assert_eq!(active(&state), vec![0, 1]);
+        // dropped after the active ones
+        state.set_order_and_active([0, 1, 2, 3], |item| [0, 1].contains(item));
+        assert_eq!(order(&state), vec![0, 1, 2, 3]);
+        assert_eq!(active(&state), vec![0, 1]);
+        // an active one dropped before the others
+        state.set_order_and_active([2, 1, 0, 3], |item| [0, 1].contains(item));
+        assert_eq!(order(&state), vec![2, 1, 0, 3]);
+        assert_eq!(active(&state), vec![1, 0]);
+    }
+
+    #[test]
+    fn set_order_and_active_without_active() {
+        let mut state = state(&[0, 1, 2], 1, 2);
+        state.activate(&2);
+        state.set_order_and_active([2, 0, 1], |_| false);
+        assert_eq!(order(&state), vec![2, 0, 1]);
+        assert!(active(&state).is_empty());
+    }
+
+    #[test]
+    fn cycle_on_empty_does_nothing() {
+        let mut state = state(&[], 1, 3);
+        assert!(!state.rotate_next());
+        assert!(!state.rotate_previous());
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Push,
+        Remove(usize),
+        Activate(usize),
+        Deactivate(usize),
+        Next,
+        Previous,
+        SetLimits(u16, u16),
+        /// moves an item to a position keeping the active ones
+        Drop(usize, usize),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            Just(Op::Push),
+            (0..12usize).prop_map(Op::Remove),
+            (0..12usize).prop_map(Op::Activate),
+            (0..12usize).prop_map(Op::Deactivate),
+            Just(Op::Next),
+            Just(Op::Previous),
+            (0..6u16, 0..8u16)
+                .prop_map(|(max_active, max_shown)| Op::SetLimits(max_active, max_shown)),
+            (0..12usize, 0..12usize).prop_map(|(item, position)| Op::Drop(item, position)),
+        ]
+    }
+
+    fn check_invariants(state: &CycleState<u32>, items: &[u32]) {
+        assert!(state.active <= state.max_active);
+        assert!(state.max_active <= state.max_shown);
+        if state.active > 0 {
+            assert!(
+                state.active_offset as usize + state.active as usize <= state.shown_len(),
+                "active items out of the shown ones: {state:?}"
+            );
+        }
+        assert_eq!(active(state).len(), state.active as usize);
+        assert!(active(state).iter().all(|item| state.is_shown(item)));
+        let mut sorted = order(state);
+        sorted.sort();
+        let mut expected = items.to_vec();
+        expected.sort();
+        assert_eq!(sorted, expected, "items lost or duplicated");
+    }
+
+    proptest! {
+        #[test]
+        fn invariants_hold(
+            max_active in 0..6u16,
+            max_shown in 0..8u16,
+            ops in prop::collection::vec(op(), 0..64),
+        ) {
+            let mut state = CycleState::new(max_active, max_shown);
+            let mut items: Vec<u32> = Vec::new();
+            let mut next_item = 0;
+            for op in ops {
+                match op {
+                    Op::Push => {
+                        state.push(next_item);
+                        items.push(next_item);
+                        next_item += 1;
+                    }
+                    Op::Remove(item) => {
+                        let item = item as u32;
+                        let removed = state.remove(&item);
+                        prop_assert_eq!(removed.is_some(), items.contains(&item));
+                        items.retain(|i| *i != item);
+                    }
+                    Op::Activate(item) => {
+                        let item = item as u32;
+                        state.activate(&item);
+                        if items.contains(&item) && state.max_active > 0 {
+                            prop_assert!(state.is_active(&item));
+                            prop_assert!(state.is_shown(&item));
+                        }
+                    }
+                    Op::Deactivate(item) => {
+                        let item = item as u32;
+                        state.deactivate(&item);
+                        prop_assert!(!state.is_active(&item));
+                    }
+                    Op::Next => {
+                        state.rotate_next();
+                    }
+                    Op::Previous => {
+                        state.rotate_previous();
+                    }
+                    Op::SetLimits(max_active, max_shown) => {
+                        state.set_limits(max_active, max_shown);
+                    }
+                    Op::Drop(item, position) => {
+                        let item = item as u32;
+                        let active = active(&state);
+                        let mut new_order: Vec<u32> =
+                            order(&state).into_iter().filter(|i| *i != item).collect();
+                        if items.contains(&item) {
+                            new_order.insert(position.min(new_order.len()), item);
+                        }
+                        state.set_order_and_active(new_order, |i| active.contains(i));
+                        prop_assert_eq!(self::active(&state).len(), active.len());
+                    }
+                }
+                check_invariants(&state, &items);
+            }
+        }
+    }
+}
@@ -0,0 +1,115 @@
+use std::rc::Rc;
+
+use dynisland_core::{
+    abi::{gtk, module::ActivityIdentifier},
+    graphics::activity_widget::ActivityWidget,
+};
+
+use super::{
+    fixed_state::FixedState, same_activities, update_ui, ActivityContainer, OrderStrategy,
+    WidgetOrderManager,
+};
+use crate::config::DynamicLayoutConfig;
+
+/// Keeps the activities in the order they were given, activating one never moves it.
+///
+/// When there are already `max_active` active activities, the one that was activated first is deactivated.
+#[derive(Debug)]
+pub struct FixedOrder {
+    pub(crate) ui: ActivityContainer,
+    pub(crate) state: FixedState<Rc<ActivityIdentifier>>,
+}
+impl FixedOrder {
+    pub fn new(config: &DynamicLayoutConfig, window: &gtk::Window, container: &gtk::Box) -> Self {
+        FixedOrder {
+            ui: ActivityContainer::new(window, container),
+            state: FixedState::new(config.max_active, config.max_activities),
+        }
+    }
+}
+
+impl WidgetOrderManager for FixedOrder {
+    fn strategy(&self) -> OrderStrategy {
+        OrderStrategy::Fixed
+    }
+
+    fn activity_container(&self) -> &ActivityContainer {
+        &self.ui
+    }
+
+    fn activity_container_mut(&mut self) -> &mut ActivityContainer {
+        &mut self.ui
+    }
+
+    fn is_active(&self, id: &ActivityIdentifier) -> bool {
+        self.state.is_active(id)
+    }
+
+    fn is_shown(&self, id: &ActivityIdentifier) -> bool {
+        self.state.is_shown(id)
+    }
+
+    fn list_activities(&self) -> Vec<Rc<ActivityIdentifier>> {
+        self.state.order.clone()
+    }
+
+    fn update_order(&mut self, order: Vec<&ActivityIdentifier>) {
+        if !same_activities(self.state.order.iter(), &order) {
+            return;
+        }
+        self.state
+            .set_order(order.into_iter().map(|id| Rc::new(id.clone())));
+        update_ui(self);
+    }
+
+    fn update_config(&mut self, max_active: u16, max_shown: u16) {
+        self.state.set_limits(max_active, max_shown);
+        update_ui(self);
+    }
+
+    fn add(&mut self, id: &ActivityIdentifier, widget: ActivityWidget) {
+        if self.ui.widget_map.borrow().contains_key(id) {
+            return;
+        }
+        let shared_id = Rc::new(id.clone());
+        self.ui
+            .widget_map
+            .borrow_mut()
+            .insert(shared_id.clone(), widget);
+        self.state.push(shared_id);
+        update_ui(self);
+    }
+
+    fn remove(&mut self, id: &ActivityIdentifier) {
+        if self.state.remove(id).is_none() {
+            return;
+        }
+        self.ui.widget_map.borrow_mut().remove(id);
+        update_ui(self);
+    }
+
+    /// only the shown activities can be activated
+    fn activate(&mut self, id: &ActivityIdentifier) -> bool {
+        if !self.state.activate(id) {
+            return false;
+        }
+        update_ui(self);
+        true
+    }
+
+    fn deactivate(&mut self, id: &ActivityIdentifier) -> bool {
+        if !self.state.deactivate(id) {
+            return false;
+        }
+        update_ui(self);
+        true
+    }
+
+    fn next(&mut self) -> bool {
+        false
+    }
+
+    fn previous(&mut self) -> bool {
+        false
+    }
+}
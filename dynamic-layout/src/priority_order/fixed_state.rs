@@ -0,0 +1,283 @@
+use std::{borrow::Borrow, collections::VecDeque};
+
+/// Order of a [`FixedOrder`](super::fixed_order::FixedOrder) without the widgets.
+///
+/// The first `max_shown` items are shown, only they can be active and activating one never
+/// moves it.
+#[derive(Debug, Clone)]
+pub struct FixedState<T> {
+    pub(crate) order: Vec<T>,
+    /// least recently activated first
+    pub(crate) active: VecDeque<T>,
+    pub(crate) max_shown: u16,
+    pub(crate) max_active: u16,
+}
+
+impl<T: Clone> FixedState<T> {
+    pub fn new(max_active: u16, max_shown: u16) -> Self {
+        FixedState {
+            order: Vec::new(),
+            active: VecDeque::new(),
+            max_shown,
+            max_active: max_active.min(max_shown),
+        }
+    }
+
+    pub fn position<Q>(&self, id: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        self.order.iter().position(|t| t.borrow() == id)
+    }
+
+    pub fn is_active<Q>(&self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        self.active.iter().any(|t| t.borrow() == id)
+    }
+
+    pub fn is_shown<Q>(&self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        match self.position(id) {
+            Some(pos) => pos < self.max_shown as usize,
+            None => false,
+        }
+    }
+
+    /// The least recently activated items are deactivated if there are too many
+    /// or if they are no longer shown
+    pub fn set_limits(&mut self, max_active: u16, max_shown: u16)
+    where
+        T: PartialEq,
+    {
+        self.max_shown = max_shown;
+        self.max_active = max_active.min(max_shown);
+        while self.active.len() > self.max_active as usize {
+            self.active.pop_front();
+        }
+        self.deactivate_hidden();
+    }
+
+    /// Replaces the order, `order` must contain the same items.
+    /// The active items that are no longer shown are deactivated
+    pub fn set_order(&mut self, order: impl IntoIterator<Item = T>)
+    where
+        T: PartialEq,
+    {
+        self.order = order.into_iter().collect();
+        self.deactivate_hidden();
+    }
+
+    pub fn push(&mut self, item: T) {
+        self.order.push(item);
+    }
+
+    pub fn remove<Q>(&mut self, id: &Q) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        let pos = self.position(id)?;
+        self.active.retain(|t| t.borrow() != id);
+        Some(self.order.remove(pos))
+    }
+
+    /// Activates `id` if it's shown, if there are already `max_active` the least recently
+    /// activated one is deactivated. Returns false if nothing changed
+    pub fn activate<Q>(&mut self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        if self.is_active(id) || !self.is_shown(id) || self.max_active == 0 {
+            return false;
+        }
+        let item = self.order[self.position(id).unwrap()].clone();
+        if self.active.len() >= self.max_active as usize {
+            self.active.pop_front();
+        }
+        self.active.push_back(item);
+        true
+    }
+
+    /// Returns false if `id` wasn't active
+    pub fn deactivate<Q>(&mut self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        if !self.is_active(id) {
+            return false;
+        }
+        self.active.retain(|t| t.borrow() != id);
+        true
+    }
+
+    fn deactivate_hidden(&mut self)
+    where
+        T: PartialEq,
+    {
+        let shown = &self.order[..self.order.len().min(self.max_shown as usize)];
+        self.active.retain(|t| shown.contains(t));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    fn state(items: &[u32], max_active: u16, max_shown: u16) -> FixedState<u32> {
+        let mut state = FixedState::new(max_active, max_shown);
+        for item in items {
+            state.push(*item);
+        }
+        state
+    }
+
+    fn active(state: &FixedState<u32>) -> Vec<u32> {
+        state.active.iter().copied().collect()
+    }
+
+    #[test]
+    fn activate_never_moves() {
+        let mut state = state(&[0, 1, 2, 3], 2, 4);
+        assert!(state.activate(&2));
+        assert!(state.activate(&0));
+        assert_eq!(state.order, vec![0, 1, 2, 3]);
+        assert_eq!(active(&state), vec![2, 0]);
+        assert!(!state.activate(&0));
+    }
+
+    #[test]
+    fn activate_deactivates_the_least_recent_one() {
+        let mut state = state(&[0, 1, 2, 3], 2, 4);
+        state.activate(&1);
+        state.activate(&2);
+        assert!(state.activate(&3));
+        assert_eq!(active(&state), vec![2, 3]);
+    }
+
+    #[test]
+    fn activate_hidden_does_nothing() {
+        let mut state = state(&[0, 1, 2, 3], 1, 2);
+        assert!(!state.activate(&3));
+        assert!(!state.activate(&4));
+        assert!(active(&state).is_empty());
+        let mut state = self::state(&[0, 1], 0, 2);
+        assert!(!state.activate(&0));
+    }
+
+    #[test]
+    fn hidden_items_are_deactivated() {
+        let mut state = state(&[0, 1, 2, 3], 2, 4);
+        state.activate(&1);
+        state.activate(&3);
+        state.set_limits(2, 3);
+        assert_eq!(active(&state), vec![1]);
+        state.set_order([1, 2, 3, 0]);
+        assert_eq!(active(&state), vec![1]);
+        state.set_order([2, 3, 0, 1]);
+        assert!(active(&state).is_empty());
+    }
+
+    #[test]
+    fn remove_deactivates() {
+        let mut state = state(&[0, 1, 2], 1, 2);
+        state.activate(&1);
+        assert_eq!(state.remove(&1), Some(1));
+        assert!(active(&state).is_empty());
+        assert!(state.is_shown(&2));
+        assert_eq!(state.remove(&1), None);
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Push,
+        Remove(usize),
+        Activate(usize),
+        Deactivate(usize),
+        SetLimits(u16, u16),
+        /// moves an item to a position
+        Move(usize, usize),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            Just(Op::Push),
+            (0..12usize).prop_map(Op::Remove),
+            (0..12usize).prop_map(Op::Activate),
+            (0..12usize).prop_map(Op::Deactivate),
+            (0..6u16, 0..8u16)
+                .prop_map(|(max_active, max_shown)| Op::SetLimits(max_active, max_shown)),
+            (0..12usize, 0..12usize).prop_map(|(item, position)| Op::Move(item, position)),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn invariants_hold(
+            max_active in 0..6u16,
+            max_shown in 0..8u16,
+            ops in prop::collection::vec(op(), 0..64),
+        ) {
+            let mut state = FixedState::new(max_active, max_shown);
+            let mut items: Vec<u32> = Vec::new();
+            let mut next_item = 0;
+            for op in ops {
+                match op {
+                    Op::Push => {
+                        state.push(next_item);
+                        items.push(next_item);
+                        next_item += 1;
+                    }
+                    Op::Remove(item) => {
+                        let item = item as u32;
+                        prop_assert_eq!(state.remove(&item).is_some(), items.contains(&item));
+                        items.retain(|i| *i != item);
+                    }
+                    Op::Activate(item) => {
+                        let item = item as u32;
+                        let before = state.order.clone();
+                        let changed = state.activate(&item);
+                        prop_assert_eq!(&state.order, &before);
+                        if changed {
+                            prop_assert!(state.is_active(&item));
+                        }
+                        if state.is_shown(&item) && state.max_active > 0 {
+                            prop_assert!(state.is_active(&item));
+                        }
+                    }
+                    Op::Deactivate(item) => {
+                        let item = item as u32;
+                        state.deactivate(&item);
+                        prop_assert!(!state.is_active(&item));
+                    }
+                    Op::SetLimits(max_active, max_shown) => {
+                        state.set_limits(max_active, max_shown);
+                    }
+                    Op::Move(item, position) => {
+                        let item = item as u32;
+                        let mut order: Vec<u32> =
+                            state.order.iter().copied().filter(|i| *i != item).collect();
+                        if items.contains(&item) {
+                            order.insert(position.min(order.len()), item);
+                        }
+                        state.set_order(order);
+                    }
+                }
+                prop_assert!(state.active.len() <= state.max_active as usize);
+                prop_assert!(state.active.iter().all(|item| state.is_shown(item)));
+                let mut sorted = state.order.clone();
+                sorted.sort();
+                prop_assert_eq!(&sorted, &items, "items lost or duplicated");
+            }
+        }
+    }
+}
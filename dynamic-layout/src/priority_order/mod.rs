@@ -1,23 +1,387 @@
 pub mod cycle_order;
-// pub mod ui_update;
-
-// pub trait WidgetOrderManager: Debug + Default {
-//     fn is_active(&self, id: &ActivityIdentifier) -> bool;
-//     fn is_shown(&self, id: &ActivityIdentifier) -> bool;
-//     fn get_container(&self) -> gtk::Box;
-//     fn get_widget_map(&self) -> Rc<RefCell<HashMap<Rc<ActivityIdentifier>, ActivityWidget>>>;
-//     fn get_window(&self) -> gtk::Window;
-//     fn list_activities(&self) -> Vec<Rc<ActivityIdentifier>>;
-//     fn update_order(&mut self, order: Vec<&ActivityIdentifier>);
-//     fn update_config(&mut self, max_active: u16, max_shown: u16);
-//     /// NOTE: `id` is implicitly hidden and deactivated if UiUpdate is None
-//     fn add(&mut self, id: &ActivityIdentifier, widget: ActivityWidget);
-//     /// NOTE: `id` is implicitly deactivated and hidden
-//     fn remove(&mut self, id: &ActivityIdentifier);
-//     /// NOTE: `id` is implicitly shown and activated
-//     fn activate(&mut self, id: &ActivityIdentifier);
-//     /// NOTE: `id` is implicitly deactivated but not hidden
-//     fn deactivate(&mut self, id: &ActivityIdentifier);
-//     fn next(&mut self);
-//     fn previous(&mut self);
-// }
+pub mod cycle_state;
+pub mod fixed_order;
+pub mod fixed_state;
+pub mod score_order;
+pub mod score_state;
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    fmt::Debug,
+    rc::Rc,
+    time::Duration,
+};
+
+use dynisland_core::{
+    abi::{gdk, glib, gtk, module::ActivityIdentifier},
+    graphics::activity_widget::{boxed_activity_mode::ActivityMode, ActivityWidget},
+};
+use gdk::prelude::ListModelExtManual;
+use gtk::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{config::DynamicLayoutConfig, popup::ActivityPopup, window_position::Orientation};
+use cycle_order::CycleOrder;
+use fixed_order::FixedOrder;
+use score_order::ScoreOrder;
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "OrderStrategy")]
+pub enum OrderStrategy {
+    /// activities can be cycled and the activated ones are moved next to each other
+    #[default]
+    #[serde(alias = "cycle")]
+    Cycle,
+    /// activities always keep the order they were given
+    #[serde(alias = "fixed")]
+    Fixed,
+    /// activities are sorted by how recently and how often they were promoted
+    #[serde(alias = "priority")]
+    Priority,
+}
+
+pub trait WidgetOrderManager: Debug {
+    fn strategy(&self) -> OrderStrategy;
+    fn activity_container(&self) -> &ActivityContainer;
+    fn activity_container_mut(&mut self) -> &mut ActivityContainer;
+    fn is_active(&self, id: &ActivityIdentifier) -> bool;
+    fn is_shown(&self, id: &ActivityIdentifier) -> bool;
+    fn list_activities(&self) -> Vec<Rc<ActivityIdentifier>>;
+    fn update_order(&mut self, order: Vec<&ActivityIdentifier>);
+    fn update_config(&mut self, max_active: u16, max_shown: u16);
+    /// Changes the order and then restores the activities in `active`
+    fn set_order_and_active(
+        &mut self,
+        order: Vec<&ActivityIdentifier>,
+        active: &[Rc<ActivityIdentifier>],
+    ) {
+        self.update_order(order);
+        for id in self.list_activities() {
+            if self.is_active(&id) && !active.contains(&id) {
+                self.deactivate(&id);
+            }
+        }
+        for id in active {
+            if !self.is_active(id) {
+                self.activate(id);
+            }
+        }
+    }
+    /// NOTE: `id` is implicitly deactivated
+    fn add(&mut self, id: &ActivityIdentifier, widget: ActivityWidget);
+    /// NOTE: `id` is implicitly deactivated and hidden
+    fn remove(&mut self, id: &ActivityIdentifier);
+    /// NOTE: `id` is implicitly shown if the strategy allows it,
+    /// returns false if nothing changed
+    fn activate(&mut self, id: &ActivityIdentifier) -> bool;
+    /// NOTE: `id` is implicitly deactivated but not hidden,
+    /// returns false if it wasn't active
+    fn deactivate(&mut self, id: &ActivityIdentifier) -> bool;
+    /// Returns false if the strategy can't cycle or there are no activities
+    fn next(&mut self) -> bool;
+    fn previous(&mut self) -> bool;
+    /// Raises the priority of `id`, returns after how much time the order should be refreshed
+    fn promote(&mut self, _id: &ActivityIdentifier) -> Option<Duration> {
+        None
+    }
+    /// Returns after how much time the order changes by itself and should be refreshed
+    fn refresh_after(&self) -> Option<Duration> {
+        None
+    }
+    fn set_promotion_decay(&mut self, _decay: Duration) {}
+    /// Updates the order if it depends on time
+    fn refresh(&mut self) {}
+
+    fn get_container(&self) -> gtk::Box {
+        self.activity_container().container.clone()
+    }
+    fn get_widget_map(&self) -> Rc<RefCell<HashMap<Rc<ActivityIdentifier>, ActivityWidget>>> {
+        self.activity_container().widget_map.clone()
+    }
+    fn get_window(&self) -> gtk::Window {
+        self.activity_container().window.clone()
+    }
+    fn add_css_class(&mut self, class: &str) -> bool {
+        self.activity_container_mut().add_css_class(class)
+    }
+    fn remove_css_class(&mut self, class: &str) -> bool {
+        self.activity_container_mut().remove_css_class(class)
+    }
+    fn list_css_classes(&self) -> Vec<String> {
+        self.activity_container().css_classes.clone()
+    }
+    fn is_collapsed(&self) -> bool {
+        self.activity_container().collapsed
+    }
+    fn set_css_classes(&mut self, classes: Vec<&str>) {
+        self.activity_container_mut().set_css_classes(classes)
+    }
+}
+
+pub fn new_order_manager(
+    config: &DynamicLayoutConfig,
+    window: &gtk::Window,
+    container: &gtk::Box,
+) -> Rc<RefCell<dyn WidgetOrderManager>> {
+    match config.order_strategy {
+        OrderStrategy::Cycle => Rc::new(RefCell::new(CycleOrder::new(config, window, container))),
+        OrderStrategy::Fixed => Rc::new(RefCell::new(FixedOrder::new(config, window, container))),
+        OrderStrategy::Priority => {
+            Rc::new(RefCell::new(ScoreOrder::new(config, window, container)))
+        }
+    }
+}
+
+/// Activates `id` and refreshes the order when it changes by itself,
+/// like when the promotion given to a hidden activity expires. Returns false if nothing changed
+pub(crate) fn activate_and_refresh(
+    ord: &Rc<RefCell<dyn WidgetOrderManager>>,
+    id: &ActivityIdentifier,
+) -> bool {
+    if !ord.borrow_mut().activate(id) {
+        return false;
+    }
+    let refresh_after = ord.borrow().refresh_after();
+    schedule_refresh(ord, refresh_after);
+    true
+}
+
+/// Refreshes the order of `ord` after `refresh_after`
+pub(crate) fn schedule_refresh(
+    ord: &Rc<RefCell<dyn WidgetOrderManager>>,
+    refresh_after: Option<Duration>,
+) {
+    if let Some(refresh_after) = refresh_after {
+        let ord = ord.clone();
+        glib::timeout_add_local_once(refresh_after, move || {
+            ord.borrow_mut().refresh();
+        });
+    }
+}
+
+/// Widgets and window shared by every order manager
+#[derive(Debug)]
+pub struct ActivityContainer {
+    pub(crate) window: gtk::Window,
+    pub(crate) container: gtk::Box,
+    pub(crate) widget_map: Rc<RefCell<HashMap<Rc<ActivityIdentifier>, ActivityWidget>>>,
+    pub(crate) css_classes: Vec<String>,
+    /// every activity is hidden because the window was idle
+    pub(crate) collapsed: bool,
+    /// number of activities in a row (or column if vertical), 0 or 1 for a single line
+    pub(crate) grid_columns: u16,
+    pub(crate) orientation: Orientation,
+    /// activity shown in a separate window while it's expanded
+    pub(crate) popup: Option<ActivityPopup>,
+}
+
+impl ActivityContainer {
+    pub fn new(window: &gtk::Window, container: &gtk::Box) -> Self {
+        Self {
+            window: window.clone(),
+            container: container.clone(),
+            widget_map: Rc::new(RefCell::new(HashMap::new())),
+            css_classes: Vec::new(),
+            collapsed: false,
+            grid_columns: 0,
+            orientation: Orientation::Horizontal,
+            popup: None,
+        }
+    }
+
+    /// Returns the widget that takes the place of `id` in the container,
+    /// the placeholder if the activity is in the popup
+    pub fn row_widget(&self, id: &ActivityIdentifier, widget: &ActivityWidget) -> gtk::Widget {
+        match &self.popup {
+            Some(popup) if popup.activity.as_ref() == id => popup.placeholder.clone().upcast(),
+            _ => widget.clone().upcast(),
+        }
+    }
+
+    fn in_popup(&self, id: &ActivityIdentifier) -> bool {
+        self.popup
+            .as_ref()
+            .is_some_and(|popup| popup.activity.as_ref() == id)
+    }
+
+    pub fn add_css_class(&mut self, class: &str) -> bool {
+        if self.container.has_css_class(class) {
+            return false;
+        }
+        self.container.add_css_class(class);
+        self.css_classes.push(class.to_string());
+        true
+    }
+
+    pub fn remove_css_class(&mut self, class: &str) -> bool {
+        if !self.css_classes.contains(&class.to_string()) {
+            return false;
+        }
+        self.container.remove_css_class(class);
+        self.css_classes.retain(|c| c != class);
+        true
+    }
+
+    pub fn set_css_classes(&mut self, classes: Vec<&str>) {
+        for class in self.css_classes.clone() {
+            self.container.remove_css_class(&class);
+        }
+        self.css_classes.clear();
+        for class in classes {
+            self.container.add_css_class(class);
+            self.css_classes.push(class.to_string());
+        }
+    }
+}
+
+/// Returns true if `order` contains exactly the activities in `current`
+pub(crate) fn same_activities<'a>(
+    current: impl Iterator<Item = &'a Rc<ActivityIdentifier>>,
+    order: &[&ActivityIdentifier],
+) -> bool {
+    let mut current = current
+        .map(|s| s.as_ref())
+        .collect::<Vec<&ActivityIdentifier>>();
+    if current.len() != order.len() {
+        return false;
+    }
+    current.sort();
+    let mut other = order.to_vec();
+    other.sort();
+    current == other
+}
+
+/// Length and thickness of a collapsed container, it keeps a size so the mouse can still reach it
+const COLLAPSED_SIZE: (i32, i32) = (60, 6);
+
+/// Hides every activity of the window and adds the `collapsed` class to the container,
+/// or shows them again
+pub(crate) fn set_collapsed(ord: &mut dyn WidgetOrderManager, collapsed: bool) {
+    let container = ord.activity_container_mut();
+    if container.collapsed == collapsed {
+        return;
+    }
+    container.collapsed = collapsed;
+    if collapsed {
+        let (length, thickness) = COLLAPSED_SIZE;
+        match container.orientation {
+            Orientation::Horizontal => container.container.set_size_request(length, thickness),
+            Orientation::Vertical => container.container.set_size_request(thickness, length),
+        }
+        container.container.add_css_class("collapsed");
+    } else {
+        container.container.set_size_request(-1, -1);
+        container.container.remove_css_class("collapsed");
+    }
+    update_ui(ord);
+}
+
+/// Syncs the children of the container with the state of the order manager
+pub(crate) fn update_ui(ord: &dyn WidgetOrderManager) {
+    let activity_container = ord.activity_container();
+    let container = ord.get_container();
+    let widget_map = ord.get_widget_map();
+    //remove widgets
+    if container.first_child().is_none() && widget_map.borrow().is_empty() {
+        return;
+    }
+
+    let all_widgets = widget_map
+        .borrow()
+        .iter()
+        .map(|(id, widget)| activity_container.row_widget(id, widget))
+        .collect::<HashSet<gtk::Widget>>();
+    let mut to_remove = Vec::new();
+    let mut container_children = HashSet::new();
+    for widget in container
+        .observe_children()
+        .iter::<glib::Object>()
+        .flatten()
+    {
+        let widget = widget.downcast::<gtk::Widget>().unwrap();
+        container_children.insert(widget.clone());
+        if !all_widgets.contains(&widget) {
+            to_remove.push(widget);
+        }
+    }
+    for widget in to_remove {
+        container.remove(&widget);
+    }
+
+    //add widgets
+    for widget in all_widgets {
+        if !container_children.contains(&widget) {
+            container.append(&widget);
+        }
+    }
+
+    //reorder and activate/deactivate widgets
+    let mut last_widget = None::<gtk::Widget>;
+    let widget_map = widget_map.borrow();
+    let order = ord.list_activities();
+    let collapsed = ord.is_collapsed();
+    for widget_id in order.iter() {
+        let widget = widget_map.get(widget_id.as_ref()).unwrap();
+        let row_widget = activity_container.row_widget(widget_id, widget);
+        container.reorder_child_after(&row_widget, last_widget.as_ref());
+
+        let shown = ord.is_shown(widget_id) && !collapsed;
+        // an activity in the popup keeps its mode until it's minimized or hidden
+        if !(shown && activity_container.in_popup(widget_id)) {
+            if ord.is_active(widget_id) {
+                widget.set_mode(ActivityMode::Compact);
+            } else {
+                widget.set_mode(ActivityMode::Minimal);
+            }
+        }
+        if shown {
+            row_widget.set_visible(true);
+            row_widget.remove_css_class("hidden");
+        } else {
+            row_widget.add_css_class("hidden");
+        }
+        last_widget = Some(row_widget);
+    }
+    place_in_grid(ord);
+}
+
+/// Puts every widget in its cell if the container uses a grid,
+/// the activities fill the rows (or the columns if vertical) following the order
+fn place_in_grid(ord: &dyn WidgetOrderManager) {
+    let activity_container = ord.activity_container();
+    let columns = activity_container.grid_columns as i32;
+    if columns <= 1 {
+        return;
+    }
+    let grid = match activity_container
+        .container
+        .layout_manager()
+        .and_then(|manager| manager.downcast::<gtk::GridLayout>().ok())
+    {
+        Some(grid) => grid,
+        None => return,
+    };
+    let widget_map = activity_container.widget_map.borrow();
+    for (idx, widget_id) in ord.list_activities().iter().enumerate() {
+        let widget = match widget_map.get(widget_id.as_ref()) {
+            Some(widget) => widget,
+            None => continue,
+        };
+        let (row, column) = grid_cell(idx as i32, columns, activity_container.orientation);
+        let layout_child = grid
+            .layout_child(&activity_container.row_widget(widget_id, widget))
+            .downcast::<gtk::GridLayoutChild>()
+            .unwrap();
+        layout_child.set_row(row);
+        layout_child.set_column(column);
+    }
+}
+
+/// Returns the row and the column of the activity at `idx`
+fn grid_cell(idx: i32, columns: i32, orientation: Orientation) -> (i32, i32) {
+    match orientation {
+        Orientation::Horizontal => (idx / columns, idx % columns),
+        Orientation::Vertical => (idx % columns, idx / columns),
+    }
+}
@@ -0,0 +1,155 @@
+use std::{
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+use dynisland_core::{
+    abi::{gtk, module::ActivityIdentifier},
+    graphics::activity_widget::ActivityWidget,
+};
+
+use super::{
+    same_activities, score_state::ScoreState, update_ui, ActivityContainer, OrderStrategy,
+    WidgetOrderManager,
+};
+use crate::config::DynamicLayoutConfig;
+
+/// Sorts the activities by a priority score, every promotion adds 1 to the score of an activity
+/// and it linearly decays to 0 in `decay`.
+///
+/// Activities with the same score keep the order they were given, the active ones are always shown.
+#[derive(Debug)]
+pub struct ScoreOrder {
+    pub(crate) ui: ActivityContainer,
+    pub(crate) state: ScoreState<Rc<ActivityIdentifier>>,
+}
+impl ScoreOrder {
+    pub fn new(config: &DynamicLayoutConfig, window: &gtk::Window, container: &gtk::Box) -> Self {
+        ScoreOrder {
+            ui: ActivityContainer::new(window, container),
+            state: ScoreState::new(
+                config.max_active,
+                config.max_activities,
+                Duration::from_millis(config.promotion_decay),
+            ),
+        }
+    }
+}
+
+impl WidgetOrderManager for ScoreOrder {
+    fn strategy(&self) -> OrderStrategy {
+        OrderStrategy::Priority
+    }
+
+    fn activity_container(&self) -> &ActivityContainer {
+        &self.ui
+    }
+
+    fn activity_container_mut(&mut self) -> &mut ActivityContainer {
+        &mut self.ui
+    }
+
+    fn is_active(&self, id: &ActivityIdentifier) -> bool {
+        self.state.is_active(id)
+    }
+
+    fn is_shown(&self, id: &ActivityIdentifier) -> bool {
+        self.state.is_shown(id)
+    }
+
+    fn list_activities(&self) -> Vec<Rc<ActivityIdentifier>> {
+        self.state.sorted.clone()
+    }
+
+    fn update_order(&mut self, order: Vec<&ActivityIdentifier>) {
+        if !same_activities(self.state.order.iter(), &order) {
+            return;
+        }
+        self.state.set_order(
+            order.into_iter().map(|id| Rc::new(id.clone())),
+            Instant::now(),
+        );
+        update_ui(self);
+    }
+
+    fn update_config(&mut self, max_active: u16, max_shown: u16) {
+        self.state.set_limits(max_active, max_shown, Instant::now());
+        update_ui(self);
+    }
+
+    fn add(&mut self, id: &ActivityIdentifier, widget: ActivityWidget) {
+        if self.ui.widget_map.borrow().contains_key(id) {
+            return;
+        }
+        let shared_id = Rc::new(id.clone());
+        self.ui
+            .widget_map
+            .borrow_mut()
+            .insert(shared_id.clone(), widget);
+        self.state.push(shared_id, Instant::now());
+        update_ui(self);
+    }
+
+    fn remove(&mut self, id: &ActivityIdentifier) {
+        if self.state.remove(id, Instant::now()).is_none() {
+            return;
+        }
+        self.ui.widget_map.borrow_mut().remove(id);
+        update_ui(self);
+    }
+
+    fn activate(&mut self, id: &ActivityIdentifier) -> bool {
+        if !self.state.activate(id, Instant::now()) {
+            return false;
+        }
+        update_ui(self);
+        true
+    }
+
+    fn deactivate(&mut self, id: &ActivityIdentifier) -> bool {
+        if !self.state.deactivate(id, Instant::now()) {
+            return false;
+        }
+        update_ui(self);
+        true
+    }
+
+    fn next(&mut self) -> bool {
+        if !self.state.rotate_next(Instant::now()) {
+            return false;
+        }
+        update_ui(self);
+        true
+    }
+
+    fn previous(&mut self) -> bool {
+        if !self.state.rotate_previous(Instant::now()) {
+            return false;
+        }
+        update_ui(self);
+        true
+    }
+
+    fn promote(&mut self, id: &ActivityIdentifier) -> Option<Duration> {
+        let now = Instant::now();
+        if !self.state.promote(id, now) {
+            return None;
+        }
+        update_ui(self);
+        self.state.expires_in(now)
+    }
+
+    fn refresh_after(&self) -> Option<Duration> {
+        self.state.expires_in(Instant::now())
+    }
+
+    fn set_promotion_decay(&mut self, decay: Duration) {
+        self.state.set_decay(decay, Instant::now());
+        update_ui(self);
+    }
+
+    fn refresh(&mut self) {
+        self.state.sort(Instant::now());
+        update_ui(self);
+    }
+}
@@ -0,0 +1,477 @@
+use std::{
+    borrow::Borrow,
+    collections::{HashMap, VecDeque},
+    hash::Hash,
+    time::{Duration, Instant},
+};
+
+/// Order of a [`ScoreOrder`](super::score_order::ScoreOrder) without the widgets.
+///
+/// Every promotion adds 1 to the score of an item and it linearly decays to 0 in `decay`,
+/// the items are sorted by score and the ones with the same score keep the order they were given.
+/// The active items are always in the first `max_shown`.
+#[derive(Debug, Clone)]
+pub struct ScoreState<T> {
+    /// order of the items without promotions
+    pub(crate) order: VecDeque<T>,
+    /// order of the items sorted by score, this is the one shown
+    pub(crate) sorted: Vec<T>,
+    pub(crate) promotions: HashMap<T, Vec<Instant>>,
+    /// least recently activated first
+    pub(crate) active: VecDeque<T>,
+    pub(crate) max_shown: u16,
+    pub(crate) max_active: u16,
+    pub(crate) decay: Duration,
+}
+
+impl<T: Clone + Eq + Hash> ScoreState<T> {
+    pub fn new(max_active: u16, max_shown: u16, decay: Duration) -> Self {
+        ScoreState {
+            order: VecDeque::new(),
+            sorted: Vec::new(),
+            promotions: HashMap::new(),
+            active: VecDeque::new(),
+            max_shown,
+            max_active: max_active.min(max_shown),
+            decay,
+        }
+    }
+
+    pub fn position<Q>(&self, id: &Q) -> Option<usize>
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        self.sorted.iter().position(|t| t.borrow() == id)
+    }
+
+    pub fn is_active<Q>(&self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        self.active.iter().any(|t| t.borrow() == id)
+    }
+
+    pub fn is_shown<Q>(&self, id: &Q) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        match self.position(id) {
+            Some(pos) => pos < self.max_shown as usize,
+            None => false,
+        }
+    }
+
+    /// Sum of the promotions of `id`, each one is 1 when it's given and 0 after `decay`
+    pub fn score<Q>(&self, id: &Q, now: Instant) -> f64
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let decay = self.decay.as_secs_f64();
+        if decay <= 0.0 {
+            return 0.0;
+        }
+        self.promotions
+            .get(id)
+            .map(|promotions| {
+                promotions
+                    .iter()
+                    .map(|time| (1.0 - now.duration_since(*time).as_secs_f64() / decay).max(0.0))
+                    .sum()
+            })
+            .unwrap_or(0.0)
+    }
+
+    /// Returns after how much time the last promotion expires, the order should be refreshed then
+    pub fn expires_in(&self, now: Instant) -> Option<Duration> {
+        self.promotions
+            .values()
+            .flatten()
+            .map(|time| (*time + self.decay).saturating_duration_since(now))
+            .max()
+    }
+
+    /// The least recently activated items are deactivated if there are too many
+    pub fn set_limits(&mut self, max_active: u16, max_shown: u16, now: Instant) {
+        self.max_shown = max_shown;
+        self.max_active = max_active.min(max_shown);
+        while self.active.len() > self.max_active as usize {
+            self.active.pop_front();
+        }
+        self.sort(now);
+    }
+
+    pub fn set_decay(&mut self, decay: Duration, now: Instant) {
+        self.decay = decay;
+        self.sort(now);
+    }
+
+    /// Replaces the order without promotions, `order` must contain the same items
+    pub fn set_order(&mut self, order: impl IntoIterator<Item = T>, now: Instant) {
+        self.order = order.into_iter().collect();
+        self.sort(now);
+    }
+
+    pub fn push(&mut self, item: T, now: Instant) {
+        self.order.push_back(item);
+        self.sort(now);
+    }
+
+    pub fn remove<Q>(&mut self, id: &Q, now: Instant) -> Option<T>
+    where
+        T: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let pos = self.order.iter().position(|t| t.borrow() == id)?;
+        self.active.retain(|t| t.borrow() != id);
+        self.promotions.remove(id);
+        let item = self.order.remove(pos);
+        self.sort(now);
+        item
+    }
+
+    /// Adds a promotion to `id`, returns false if there is no such item
+    pub fn promote<Q>(&mut self, id: &Q, now: Instant) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        let item = match self.order.iter().find(|t| (*t).borrow() == id) {
+            Some(item) => item.clone(),
+            None => return false,
+        };
+        self.promotions.entry(item).or_default().push(now);
+        self.sort(now);
+        true
+    }
+
+    /// Activates `id`, a hidden item is promoted and put in the shown ones. If there are already
+    /// `max_active` the least recently activated one is deactivated. Returns false if nothing
+    /// changed
+    pub fn activate<Q>(&mut self, id: &Q, now: Instant) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        if self.is_active(id) || self.max_active == 0 {
+            return false;
+        }
+        let item = match self.order.iter().find(|t| (*t).borrow() == id) {
+            Some(item) => item.clone(),
+            None => return false,
+        };
+        if !self.is_shown(id) && !self.decay.is_zero() {
+            self.promotions.entry(item.clone()).or_default().push(now);
+        }
+        if self.active.len() >= self.max_active as usize {
+            self.active.pop_front();
+        }
+        self.active.push_back(item);
+        self.sort(now);
+        true
+    }
+
+    /// Returns false if `id` wasn't active
+    pub fn deactivate<Q>(&mut self, id: &Q, now: Instant) -> bool
+    where
+        T: Borrow<Q>,
+        Q: PartialEq + ?Sized,
+    {
+        if !self.is_active(id) {
+            return false;
+        }
+        self.active.retain(|t| t.borrow() != id);
+        self.sort(now);
+        true
+    }
+
+    /// Moves the last item of the order without promotions to the front,
+    /// returns false if there are no items
+    pub fn rotate_next(&mut self, now: Instant) -> bool {
+        match self.order.pop_back() {
+            Some(back) => {
+                self.order.push_front(back);
+                self.sort(now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves the first item of the order without promotions to the back,
+    /// returns false if there are no items
+    pub fn rotate_previous(&mut self, now: Instant) -> bool {
+        match self.order.pop_front() {
+            Some(front) => {
+                self.order.push_back(front);
+                self.sort(now);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Removes the expired promotions and sorts the items by their score,
+    /// the active items are kept in the first `max_shown`
+    pub fn sort(&mut self, now: Instant) {
+        let decay = self.decay;
+        self.promotions.retain(|_, promotions| {
+            promotions.retain(|time| now.duration_since(*time) < decay);
+            !promotions.is_empty()
+        });
+        let mut sorted: Vec<(f64, T)> = self
+            .order
+            .iter()
+            .map(|item| (self.score(item, now), item.clone()))
+            .collect();
+        // sort_by is stable, so items with the same score keep their order
+        sorted.sort_by(|(a, _), (b, _)| b.total_cmp(a));
+        // the inactive items get the shown positions left by the active ones
+        let mut inactive_shown = (self.max_shown as usize).saturating_sub(self.active.len());
+        let mut shown = Vec::with_capacity(self.max_shown as usize);
+        let mut hidden = Vec::new();
+        for (_, item) in sorted {
+            if self.active.contains(&item) {
+                shown.push(item);
+            } else if inactive_shown > 0 {
+                inactive_shown -= 1;
+                shown.push(item);
+            } else {
+                hidden.push(item);
+            }
+        }
+        shown.append(&mut hidden);
+        self.sorted = shown;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    const DECAY: Duration = Duration::from_secs(10);
+
+    fn state(items: &[u32], max_active: u16, max_shown: u16, now: Instant) -> ScoreState<u32> {
+        let mut state = ScoreState::new(max_active, max_shown, DECAY);
+        for item in items {
+            state.push(*item, now);
+        }
+        state
+    }
+
+    fn secs(now: Instant, secs: f64) -> Instant {
+        now + Duration::from_secs_f64(secs)
+    }
+
+    #[test]
+    fn score_decays_linearly() {
+        let now = Instant::now();
+        let mut state = state(&[0, 1], 1, 2, now);
+        state.promote(&1, now);
+        assert_eq!(state.score(&1, now), 1.0);
+        assert_eq!(state.score(&1, secs(now, 2.5)), 0.75);
+        state.promote(&1, secs(now, 5.0));
+        assert_eq!(state.score(&1, secs(now, 5.0)), 1.5);
+        assert_eq!(state.score(&1, secs(now, 10.0)), 0.5);
+        assert_eq!(state.score(&1, secs(now, 20.0)), 0.0);
+        assert_eq!(state.score(&0, now), 0.0);
+    }
+
+    #[test]
+    fn promoted_items_go_first() {
+        let now = Instant::now();
+        let mut state = state(&[0, 1, 2, 3], 1, 2, now);
+        state.promote(&2, now);
+        assert_eq!(state.sorted, vec![2, 0, 1, 3]);
+        state.promote(&3, secs(now, 1.0));
+        assert_eq!(state.sorted, vec![3, 2, 0, 1]);
+    }
+
+    #[test]
+    fn equal_scores_keep_the_order() {
+        let now = Instant::now();
+        let mut state = state(&[0, 1, 2, 3], 1, 4, now);
+        state.promote(&3, now);
+        state.promote(&1, now);
+        assert_eq!(state.sorted, vec![1, 3, 0, 2]);
+        state.rotate_next(now);
+        assert_eq!(state.sorted, vec![3, 1, 0, 2]);
+        state.rotate_previous(now);
+        state.rotate_previous(now);
+        assert_eq!(state.sorted, vec![1, 3, 2, 0]);
+    }
+
+    #[test]
+    fn expired_promotions_are_removed() {
+        let now = Instant::now();
+        let mut state = state(&[0, 1, 2], 1, 3, now);
+        state.promote(&2, now);
+        state.promote(&1, secs(now, 4.0));
+        assert_eq!(state.expires_in(secs(now, 4.0)), Some(DECAY));
+        state.sort(secs(now, 10.0));
+        assert!(!state.promotions.contains_key(&2));
+        assert_eq!(state.sorted, vec![1, 0, 2]);
+        assert_eq!(
+            state.expires_in(secs(now, 10.0)),
+            Some(Duration::from_secs(4))
+        );
+        state.sort(secs(now, 14.0));
+        assert!(state.promotions.is_empty());
+        assert_eq!(state.sorted, vec![0, 1, 2]);
+        assert_eq!(state.expires_in(secs(now, 14.0)), None);
+    }
+
+    #[test]
+    fn no_decay_never_reorders() {
+        let now = Instant::now();
+        let mut state = state(&[0, 1, 2], 1, 2, now);
+        state.set_decay(Duration::ZERO, now);
+        state.promote(&2, now);
+        assert_eq!(state.score(&2, now), 0.0);
+        assert!(state.promotions.is_empty());
+        assert_eq!(state.sorted, vec![0, 1, 2]);
+        // the active items are still shown
+        assert!(state.activate(&2, now));
+        assert!(state.is_shown(&2));
+        assert_eq!(state.sorted, vec![0, 2, 1]);
+        assert_eq!(state.expires_in(now), None);
+    }
+
+    #[test]
+    fn active_items_stay_shown() {
+        let now = Instant::now();
+        let mut state = state(&[0, 1, 2, 3], 1, 2, now);
+        assert!(state.activate(&3, now));
+        assert!(state.is_shown(&3));
+        assert_eq!(state.sorted, vec![3, 0, 1, 2]);
+        // the promotion of the activation expires
+        state.sort(secs(now, 10.0));
+        assert!(state.is_shown(&3));
+        assert_eq!(state.sorted, vec![0, 3, 1, 2]);
+        // items with a higher score don't push it out
+        state.promote(&1, secs(now, 11.0));
+        state.promote(&2, secs(now, 11.0));
+        assert!(state.is_shown(&3));
+        assert_eq!(state.sorted, vec![1, 3, 2, 0]);
+        assert!(state.deactivate(&3, secs(now, 11.0)));
+        assert_eq!(state.sorted, vec![1, 2, 0, 3]);
+    }
+
+    #[test]
+    fn activate_deactivates_the_least_recent_one() {
+        let now = Instant::now();
+        let mut state = state(&[0, 1, 2], 2, 3, now);
+        state.activate(&0, now);
+        state.activate(&1, now);
+        assert!(state.activate(&2, now));
+        assert!(!state.is_active(&0));
+        assert!(!state.activate(&2, now));
+        assert!(!state.activate(&5, now));
+    }
+
+    #[derive(Debug, Clone)]
+    enum Op {
+        Push,
+        Remove(usize),
+        Activate(usize),
+        Deactivate(usize),
+        Promote(usize),
+        Next,
+        Previous,
+        SetLimits(u16, u16),
+        /// time passes, in seconds
+        Wait(u64),
+    }
+
+    fn op() -> impl Strategy<Value = Op> {
+        prop_oneof![
+            Just(Op::Push),
+            (0..12usize).prop_map(Op::Remove),
+            (0..12usize).prop_map(Op::Activate),
+            (0..12usize).prop_map(Op::Deactivate),
+            (0..12usize).prop_map(Op::Promote),
+            Just(Op::Next),
+            Just(Op::Previous),
+            (0..6u16, 0..8u16)
+                .prop_map(|(max_active, max_shown)| Op::SetLimits(max_active, max_shown)),
+            (0..15u64).prop_map(Op::Wait),
+        ]
+    }
+
+    proptest! {
+        #[test]
+        fn invariants_hold(
+            max_active in 0..6u16,
+            max_shown in 0..8u16,
+            decay in 0..10u64,
+            ops in prop::collection::vec(op(), 0..64),
+        ) {
+            let mut now = Instant::now();
+            let mut state = ScoreState::new(max_active, max_shown, Duration::from_secs(decay));
+            let mut items: Vec<u32> = Vec::new();
+            let mut next_item = 0;
+            for op in ops {
+                match op {
+                    Op::Push => {
+                        state.push(next_item, now);
+                        items.push(next_item);
+                        next_item += 1;
+                    }
+                    Op::Remove(item) => {
+                        let item = item as u32;
+                        prop_assert_eq!(state.remove(&item, now).is_some(), items.contains(&item));
+                        items.retain(|i| *i != item);
+                    }
+                    Op::Activate(item) => {
+                        let item = item as u32;
+                        state.activate(&item, now);
+                        if items.contains(&item) && state.max_active > 0 {
+                            prop_assert!(state.is_active(&item));
+                        }
+                    }
+                    Op::Deactivate(item) => {
+                        let item = item as u32;
+                        state.deactivate(&item, now);
+                        prop_assert!(!state.is_active(&item));
+                    }
+                    Op::Promote(item) => {
+                        state.promote(&(item as u32), now);
+                    }
+                    Op::Next => {
+                        state.rotate_next(now);
+                    }
+                    Op::Previous => {
+                        state.rotate_previous(now);
+                    }
+                    Op::SetLimits(max_active, max_shown) => {
+                        state.set_limits(max_active, max_shown, now);
+                    }
+                    Op::Wait(secs) => {
+                        now += Duration::from_secs(secs);
+                        state.sort(now);
+                    }
+                }
+                prop_assert!(state.active.len() <= state.max_active as usize);
+                prop_assert!(state.active.iter().all(|item| state.is_shown(item)));
+                let mut sorted = state.sorted.clone();
+                sorted.sort();
+                prop_assert_eq!(&sorted, &items, "items lost or duplicated");
+                // the scores decrease in the shown items and in the hidden ones
+                let shown = state.sorted.len().min(state.max_shown as usize);
+                let (shown, hidden) = state.sorted.split_at(shown);
+                for part in [shown, hidden] {
+                    let inactive: Vec<f64> = part
+                        .iter()
+                        .filter(|item| !state.is_active(*item))
+                        .map(|item| state.score(item, now))
+                        .collect();
+                    prop_assert!(inactive.windows(2).all(|pair| pair[0] >= pair[1]));
+                }
+            }
+        }
+    }
+}
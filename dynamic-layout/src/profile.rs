@@ -0,0 +1,304 @@
+use dynisland_core::abi::{glib, log};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    layout::DynamicLayout,
+    window_position::{connected_monitors, MonitorSelector},
+};
+
+/// How often the rules with a `time` are checked
+const TIME_RULES_INTERVAL: u32 = 30;
+
+/// Arguments of the `profile` command that can't be used as profile names
+pub(crate) const RESERVED_PROFILE_NAMES: [&str; 2] = ["auto", "none"];
+
+/// Switches to `profile` when every condition that is set is true
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(default)]
+pub struct ProfileRule {
+    pub(crate) profile: String,
+    /// a connected monitor matches this selector
+    pub(crate) monitor: Option<MonitorSelector>,
+    /// a window has this css class, added with the `add-css` command
+    pub(crate) css_class: Option<String>,
+    /// the local time is in this range, `HH:MM-HH:MM`, it can go past midnight
+    pub(crate) time: Option<String>,
+}
+
+impl ProfileRule {
+    fn matches(&self, css_classes: &[String], now: Option<u32>) -> bool {
+        if let Some(selector) = &self.monitor {
+            if !connected_monitors()
+                .iter()
+                .any(|monitor| selector.matches(monitor))
+            {
+                return false;
+            }
+        }
+        if let Some(class) = &self.css_class {
+            if !css_classes.contains(class) {
+                return false;
+            }
+        }
+        if let Some(range) = &self.time {
+            let (start, end) = match parse_time_range(range) {
+                Ok(range) => range,
+                Err(_) => return false,
+            };
+            let now = match now {
+                Some(now) => now,
+                None => return false,
+            };
+            let in_range = if start <= end {
+                start <= now && now < end
+            } else {
+                now >= start || now < end
+            };
+            if !in_range {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Returns the problems of the rule, `profiles` are the names of the defined profiles
+    pub fn validate<'a>(&self, mut profiles: impl Iterator<Item = &'a String>) -> Vec<String> {
+        let mut problems = Vec::new();
+        let rule = format!("profile rule for \"{}\"", self.profile);
+        if !profiles.any(|profile| *profile == self.profile) {
+            problems.push(format!("{rule}: the profile doesn't exist"));
+        }
+        if self.monitor.is_none() && self.css_class.is_none() && self.time.is_none() {
+            problems.push(format!("{rule}: no condition, it always matches"));
+        }
+        if let Some(selector) = &self.monitor {
+            if let Err(err) = selector.check() {
+                problems.push(format!("{rule}: {err}"));
+            }
+        }
+        if let Some(range) = &self.time {
+            if let Err(err) = parse_time_range(range) {
+                problems.push(format!("{rule}: {err}"));
+            }
+        }
+        problems
+    }
+}
+
+/// Which profile is used
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub enum ProfileChoice {
+    /// the first rule that matches, or no profile
+    #[default]
+    Rules,
+    /// chosen with the `profile` command, `None` to use the config without profiles
+    Manual(Option<String>),
+}
+
+#[derive(Debug, Default)]
+pub struct ProfileState {
+    pub(crate) choice: ProfileChoice,
+    pub(crate) active: Option<String>,
+}
+
+impl DynamicLayout {
+    /// Returns the profile that should be active
+    pub(crate) fn wanted_profile(&self) -> Option<String> {
+        if let ProfileChoice::Manual(profile) = &self.profile_state.borrow().choice {
+            return profile.clone();
+        }
+        let css_classes: Vec<String> = self
+            .order_managers
+            .borrow()
+            .values()
+            .flat_map(|ord| ord.borrow().list_css_classes())
+            .collect();
+        let now = glib::DateTime::now_local()
+            .ok()
+            .map(|now| (now.hour() * 60 + now.minute()) as u32);
+        self.base_config
+            .borrow()
+            .profile_rules
+            .iter()
+            .find(|rule| rule.matches(&css_classes, now))
+            .map(|rule| rule.profile.clone())
+    }
+
+    /// Applies the profile that should be active if it changed, or anyway if `force` is true,
+    /// returns true if the config was applied
+    pub(crate) fn update_profile(&self, force: bool) -> bool {
+        let profile = self.wanted_profile();
+        if !force && self.profile_state.borrow().active == profile {
+            return false;
+        }
+        if profile != self.profile_state.borrow().active {
+            log::info!("switching to profile {:?}", profile);
+        }
+        let config = self
+            .base_config
+            .borrow()
+            .with_profile(profile.as_deref())
+            .into_main_config();
+        *self.config.borrow_mut() = config;
+        self.profile_state.borrow_mut().active = profile;
+        self.apply_config();
+        true
+    }
+
+    /// Checks the rules with a `time` periodically
+    pub(crate) fn start_profile_timer(&self) {
+        let this = self.clone();
+        glib::timeout_add_seconds_local(TIME_RULES_INTERVAL, move || {
+            let has_time_rules = this
+                .base_config
+                .borrow()
+                .profile_rules
+                .iter()
+                .any(|rule| rule.time.is_some());
+            if has_time_rules {
+                this.update_profile(false);
+            }
+            glib::ControlFlow::Continue
+        });
+    }
+}
+
+/// Parses `HH:MM-HH:MM` into minutes since midnight
+fn parse_time_range(range: &str) -> Result<(u32, u32), String> {
+    let invalid = || format!("invalid time range \"{range}\", use HH:MM-HH:MM");
+    let (start, end) = range.split_once('-').ok_or_else(invalid)?;
+    let parse_time = |time: &str| -> Option<u32> {
+        let (hours, minutes) = time.trim().split_once(':')?;
+        let hours: u32 = hours.parse().ok()?;
+        let minutes: u32 = minutes.parse().ok()?;
+        (hours < 24 && minutes < 60).then_some(hours * 60 + minutes)
+    };
+    match (parse_time(start), parse_time(end)) {
+        (Some(start), Some(end)) => Ok((start, end)),
+        _ => Err(invalid()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::DeDynamicLayoutConfigMain;
+
+    fn rule(css_class: Option<&str>, time: Option<&str>) -> ProfileRule {
+        ProfileRule {
+            profile: String::from("profile"),
+            monitor: None,
+            css_class: css_class.map(String::from),
+            time: time.map(String::from),
+        }
+    }
+
+    #[test]
+    fn time_range() {
+        assert_eq!(parse_time_range("08:30-17:00"), Ok((510, 1020)));
+        assert_eq!(parse_time_range(" 22:00 - 07:00 "), Ok((1320, 420)));
+        assert!(parse_time_range("08:30").is_err());
+        assert!(parse_time_range("24:00-07:00").is_err());
+        assert!(parse_time_range("08:60-09:00").is_err());
+        assert!(parse_time_range("8-9").is_err());
+        assert!(parse_time_range("aa:00-07:00").is_err());
+    }
+
+    #[test]
+    fn time_rule() {
+        let day = rule(None, Some("08:00-17:00"));
+        assert!(day.matches(&[], Some(8 * 60)));
+        assert!(!day.matches(&[], Some(17 * 60)));
+        assert!(!day.matches(&[], None));
+        // the range goes past midnight
+        let night = rule(None, Some("22:00-07:00"));
+        assert!(night.matches(&[], Some(23 * 60)));
+        assert!(night.matches(&[], Some(0)));
+        assert!(!night.matches(&[], Some(7 * 60)));
+        assert!(!night.matches(&[], Some(12 * 60)));
+        assert!(!rule(None, Some("invalid")).matches(&[], Some(0)));
+    }
+
+    #[test]
+    fn css_class_rule() {
+        let classes = [String::from("dnd")];
+        assert!(rule(Some("dnd"), None).matches(&classes, None));
+        assert!(!rule(Some("dnd"), None).matches(&[], None));
+        // every condition must be true
+        let both = rule(Some("dnd"), Some("22:00-07:00"));
+        assert!(both.matches(&classes, Some(23 * 60)));
+        assert!(!both.matches(&classes, Some(12 * 60)));
+        assert!(!both.matches(&[], Some(23 * 60)));
+    }
+
+    #[test]
+    fn profile_replaces_only_the_values_it_sets() {
+        let config: DeDynamicLayoutConfigMain = serde_json::from_str(
+            r#"{
+                "max_active": 2,
+                "windows": {
+                    "": { "max_activities": 4, "grid_columns": 2 },
+                    "left": { "max_active": 1 }
+                },
+                "profiles": {
+                    "docked": {
+                        "": { "max_activities": 6 },
+                        "right": { "grid_columns": 3 }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let docked = config.with_profile(Some("docked")).into_main_config();
+        assert_eq!(docked.windows[""].max_activities, 6);
+        assert_eq!(docked.windows[""].grid_columns, 2);
+        assert_eq!(docked.windows[""].max_active, 2);
+        assert_eq!(docked.windows["left"].max_active, 1);
+        assert_eq!(docked.windows["right"].grid_columns, 3);
+        assert_eq!(docked.windows["right"].max_active, 2);
+        for profile in [None, Some("missing")] {
+            let main = config.with_profile(profile).into_main_config();
+            assert_eq!(main.windows[""].max_activities, 4);
+            assert!(!main.windows.contains_key("right"));
+        }
+    }
+
+    #[test]
+    fn profile_merges_the_window_position() {
+        let config: DeDynamicLayoutConfigMain = serde_json::from_str(
+            r#"{
+                "windows": {
+                    "": { "window_position": { "margin_x": 5, "layer_shell": false } }
+                },
+                "profiles": {
+                    "docked": {
+                        "": { "window_position": { "margin_y": 7 } },
+                        "right": { "window_position": { "margin_x": 3 } }
+                    }
+                }
+            }"#,
+        )
+        .unwrap();
+        let docked = config.with_profile(Some("docked")).into_main_config();
+        let position = &docked.windows[""].window_position;
+        assert_eq!(position.margin_x, 5);
+        assert_eq!(position.margin_y, 7);
+        assert!(!position.layer_shell);
+        let position = &docked.windows["right"].window_position;
+        assert_eq!(position.margin_x, 3);
+        assert!(position.layer_shell);
+        let position = &config.into_main_config().windows[""].window_position;
+        assert_eq!(position.margin_y, 0);
+    }
+
+    #[test]
+    fn reserved_profile_names_are_invalid() {
+        let config: DeDynamicLayoutConfigMain =
+            serde_json::from_str(r#"{ "profiles": { "auto": {}, "none": {}, "docked": {} } }"#)
+                .unwrap();
+        let problems = config.validate();
+        assert_eq!(problems.len(), 2);
+        assert!(problems[0].starts_with("profile \"auto\""));
+        assert!(problems[1].starts_with("profile \"none\""));
+    }
+}
@@ -1,10 +1,15 @@
+use std::{
+    fmt::{Debug, Display, Formatter},
+    str::FromStr,
+};
+
 use dynisland_core::abi::{gdk, gtk, gtk_layer_shell, log};
 use gdk::prelude::*;
 use gtk::{prelude::*, Window};
 use gtk_layer_shell::LayerShell;
 use serde::{Deserialize, Serialize};
 
-use crate::config::WindowPosition;
+use crate::{activity_match::Pattern, config::WindowPosition};
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 #[serde(tag = "Alignment")]
@@ -15,6 +20,9 @@ pub enum Alignment {
     Center,
     #[serde(alias = "end")]
     End,
+    /// anchored to both edges, stretched like a bar
+    #[serde(alias = "fill")]
+    Fill,
 }
 
 impl Alignment {
@@ -23,10 +31,340 @@ impl Alignment {
             Alignment::Start => gtk::Align::Start,
             Alignment::Center => gtk::Align::Center,
             Alignment::End => gtk::Align::End,
+            Alignment::Fill => gtk::Align::Fill,
+        }
+    }
+
+    /// Returns if the start edge and the end edge are anchored
+    pub fn anchors(&self) -> (bool, bool) {
+        match self {
+            Alignment::Start => (true, false),
+            Alignment::Center => (false, false),
+            Alignment::End => (false, true),
+            Alignment::Fill => (true, true),
+        }
+    }
+}
+
+/// Space reserved by the window on the anchored edge, the other windows don't go over it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExclusiveZone {
+    /// `-1` puts the window over the other layers, `0` doesn't reserve any space
+    Size(i32),
+    /// the current size of the window
+    Auto,
+}
+
+impl Serialize for ExclusiveZone {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        match self {
+            ExclusiveZone::Size(size) => serializer.serialize_i32(*size),
+            ExclusiveZone::Auto => serializer.serialize_str("auto"),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for ExclusiveZone {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct ExclusiveZoneVisitor;
+        impl<'de> serde::de::Visitor<'de> for ExclusiveZoneVisitor {
+            type Value = ExclusiveZone;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(f, "a number or \"auto\"")
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> Result<Self::Value, E> {
+                i32::try_from(v)
+                    .map(ExclusiveZone::Size)
+                    .map_err(|_| E::custom(format!("exclusive zone {v} is too big")))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> Result<Self::Value, E> {
+                i32::try_from(v)
+                    .map(ExclusiveZone::Size)
+                    .map_err(|_| E::custom(format!("exclusive zone {v} is too big")))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<Self::Value, E> {
+                if v.eq_ignore_ascii_case("auto") {
+                    Ok(ExclusiveZone::Auto)
+                } else {
+                    Err(E::invalid_value(serde::de::Unexpected::Str(v), &self))
+                }
+            }
+        }
+        deserializer.deserialize_any(ExclusiveZoneVisitor)
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "Orientation")]
+pub enum Orientation {
+    #[default]
+    #[serde(alias = "horizontal")]
+    Horizontal,
+    #[serde(alias = "vertical")]
+    Vertical,
+}
+
+impl Orientation {
+    pub fn map_gtk(&self) -> gtk::Orientation {
+        match self {
+            Orientation::Horizontal => gtk::Orientation::Horizontal,
+            Orientation::Vertical => gtk::Orientation::Vertical,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "KeyboardMode")]
+pub enum KeyboardMode {
+    /// the window never gets the keyboard focus
+    #[default]
+    #[serde(alias = "none")]
+    None,
+    /// the window gets the keyboard focus when it's clicked
+    #[serde(alias = "on_demand", alias = "on-demand")]
+    OnDemand,
+    /// the window always has the keyboard focus while it's shown
+    #[serde(alias = "exclusive")]
+    Exclusive,
+}
+
+impl KeyboardMode {
+    pub fn map_gtk(&self) -> gtk_layer_shell::KeyboardMode {
+        match self {
+            KeyboardMode::None => gtk_layer_shell::KeyboardMode::None,
+            KeyboardMode::OnDemand => gtk_layer_shell::KeyboardMode::OnDemand,
+            KeyboardMode::Exclusive => gtk_layer_shell::KeyboardMode::Exclusive,
+        }
+    }
+}
+
+/// `monitor` of a window: a monitor selector (`""` lets the compositor choose),
+/// `"*"` for a window on every monitor or a list of selectors for a window on each of them.
+///
+/// A selector is a connector name (`DP-1`) or `manufacturer=`, `model=` or `description=`
+/// followed by a glob or a `/regex/`, see [`MonitorSelector`]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(untagged)]
+pub enum MonitorConfig {
+    Single(MonitorSelector),
+    List(Vec<MonitorSelector>),
+}
+
+impl Default for MonitorConfig {
+    fn default() -> Self {
+        MonitorConfig::Single(MonitorSelector::from_str("").unwrap())
+    }
+}
+
+impl MonitorConfig {
+    /// Returns true if the window should be created once for each monitor
+    pub fn is_multi(&self) -> bool {
+        match self {
+            MonitorConfig::Single(selector) => selector.source == "*",
+            MonitorConfig::List(_) => true,
+        }
+    }
+
+    /// Returns true if the compositor should choose the monitor
+    pub fn is_compositor_choice(&self) -> bool {
+        matches!(self, MonitorConfig::Single(selector) if selector.source.is_empty())
+    }
+
+    /// Returns the connected monitors that match the config,
+    /// in the order of the list or in the order of the display for `"*"`
+    pub fn matching_monitors(&self) -> Vec<gdk::Monitor> {
+        let monitors = connected_monitors();
+        match self {
+            MonitorConfig::Single(selector) if selector.source == "*" => monitors,
+            MonitorConfig::Single(selector) => {
+                find_monitor(&monitors, selector).into_iter().collect()
+            }
+            MonitorConfig::List(selectors) => {
+                let mut found: Vec<gdk::Monitor> = Vec::new();
+                for selector in selectors {
+                    // a monitor can match more than one selector, use it only once
+                    if let Some(monitor) = monitors
+                        .iter()
+                        .find(|monitor| !found.contains(monitor) && selector.matches(monitor))
+                    {
+                        found.push(monitor.clone());
+                    }
+                }
+                found
+            }
         }
     }
 }
 
+/// A monitor selector, the pattern is compiled once when the config is parsed:
+/// `manufacturer=<pattern>`, `model=<pattern>`, `description=<pattern>` or a connector name,
+/// the patterns are case insensitive globs or regexes between slashes
+#[derive(Clone)]
+pub struct MonitorSelector {
+    /// the selector written in the config
+    source: String,
+    /// an invalid selector doesn't match any monitor, the error is reported by `validate`
+    kind: Result<SelectorKind, String>,
+}
+
+#[derive(Clone)]
+enum SelectorKind {
+    Connector,
+    Manufacturer(Pattern),
+    Model(Pattern),
+    Description(Pattern),
+}
+
+impl MonitorSelector {
+    /// Returns an error if the selector uses an unknown property or an invalid pattern
+    pub fn check(&self) -> Result<(), String> {
+        match &self.kind {
+            Ok(_) => Ok(()),
+            Err(err) => Err(format!(
+                "invalid monitor selector \"{}\": {err}",
+                self.source
+            )),
+        }
+    }
+
+    pub fn matches(&self, monitor: &gdk::Monitor) -> bool {
+        let (value, pattern) = match &self.kind {
+            Ok(SelectorKind::Connector) => {
+                return match monitor.connector() {
+                    Some(connector) => connector.eq_ignore_ascii_case(&self.source),
+                    None => false,
+                }
+            }
+            Ok(SelectorKind::Manufacturer(pattern)) => (monitor.manufacturer(), pattern),
+            Ok(SelectorKind::Model(pattern)) => (monitor.model(), pattern),
+            Ok(SelectorKind::Description(pattern)) => (monitor.description(), pattern),
+            Err(_) => return false,
+        };
+        match value {
+            Some(value) => pattern.matches(&value),
+            None => false,
+        }
+    }
+}
+
+impl FromStr for MonitorSelector {
+    type Err = std::convert::Infallible;
+
+    /// Never fails, the errors are kept in the selector
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let kind = match s.split_once('=') {
+            Some(("manufacturer", pattern)) => {
+                Pattern::from_str(pattern).map(SelectorKind::Manufacturer)
+            }
+            Some(("model", pattern)) => Pattern::from_str(pattern).map(SelectorKind::Model),
+            Some(("description", pattern)) => {
+                Pattern::from_str(pattern).map(SelectorKind::Description)
+            }
+            Some((property, _)) => Err(format!(
+                "unknown property {property}, use manufacturer, model or description"
+            )),
+            None => Ok(SelectorKind::Connector),
+        };
+        Ok(MonitorSelector {
+            source: s.to_string(),
+            kind,
+        })
+    }
+}
+
+impl Display for MonitorSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.source)
+    }
+}
+
+impl Debug for MonitorSelector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}", self.source)
+    }
+}
+
+impl PartialEq for MonitorSelector {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+impl Eq for MonitorSelector {}
+
+impl Serialize for MonitorSelector {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        serializer.serialize_str(&self.source)
+    }
+}
+
+impl<'de> Deserialize<'de> for MonitorSelector {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let source = String::deserialize(deserializer)?;
+        Ok(MonitorSelector::from_str(&source).unwrap())
+    }
+}
+
+/// What to do with a window when none of its monitors is connected
+#[derive(Serialize, Deserialize, Default, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(tag = "UnmatchedMonitor")]
+pub enum UnmatchedMonitor {
+    /// let the compositor choose the monitor
+    #[default]
+    #[serde(alias = "compositor")]
+    Compositor,
+    /// use the first connected monitor
+    #[serde(alias = "first")]
+    First,
+    /// hide the window until one of its monitors is connected
+    #[serde(alias = "hide")]
+    Hide,
+}
+
+/// Where a window should be shown
+#[derive(Debug, Clone)]
+pub enum MonitorChoice {
+    Monitors(Vec<gdk::Monitor>),
+    Compositor,
+    Hidden,
+}
+
+/// Returns the monitors of the default display that already have a connector
+pub fn connected_monitors() -> Vec<gdk::Monitor> {
+    let display = match gdk::Display::default() {
+        Some(display) => display,
+        None => return Vec::new(),
+    };
+    display
+        .monitors()
+        .iter::<gdk::Monitor>()
+        .flatten()
+        .filter(|monitor| monitor.connector().is_some())
+        .collect()
+}
+
+fn find_monitor(monitors: &[gdk::Monitor], selector: &MonitorSelector) -> Option<gdk::Monitor> {
+    monitors
+        .iter()
+        .find(|monitor| selector.matches(monitor))
+        .cloned()
+}
+
 #[derive(Serialize, Deserialize, Default, Debug, Clone)]
 #[serde(tag = "Layer")]
 pub enum Layer {
@@ -52,79 +390,285 @@ impl Layer {
     }
 }
 
+/// Returns the connector of the monitor `window` is on, if it's mapped
+pub fn window_connector(window: &Window) -> Option<String> {
+    window
+        .surface()
+        .and_then(|surface| window.display().monitor_at_surface(&surface))
+        .and_then(|monitor| monitor.connector())
+        .map(|connector| connector.to_string())
+}
+
 impl WindowPosition {
-    pub fn config_layer_shell_for(&self, window: &Window) {
-        window.set_layer(self.layer.map_gtk());
-        match self.v_anchor {
-            Alignment::Start => {
-                window.set_anchor(gtk_layer_shell::Edge::Top, true);
-                window.set_anchor(gtk_layer_shell::Edge::Bottom, false);
-                window.set_margin(gtk_layer_shell::Edge::Top, self.margin_y);
-            }
-            Alignment::Center => {
-                window.set_anchor(gtk_layer_shell::Edge::Top, false);
-                window.set_anchor(gtk_layer_shell::Edge::Bottom, false);
-            }
-            Alignment::End => {
-                window.set_anchor(gtk_layer_shell::Edge::Top, false);
-                window.set_anchor(gtk_layer_shell::Edge::Bottom, true);
-                window.set_margin(gtk_layer_shell::Edge::Bottom, self.margin_y);
-            }
+    /// Returns the monitor selectors that can't be parsed and the other problems of the position,
+    /// the connected monitors don't change the result
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems: Vec<String> = self
+            .monitor_selectors()
+            .iter()
+            .filter_map(|selector| selector.check().err())
+            .collect();
+        if self.exclusive_zone == ExclusiveZone::Auto && self.exclusive_zone_for(1, 1) == 0 {
+            problems.push(String::from(
+                "exclusive_zone \"auto\" needs v_anchor or h_anchor to be start or end",
+            ));
         }
-        match self.h_anchor {
-            Alignment::Start => {
-                window.set_anchor(gtk_layer_shell::Edge::Left, true);
-                window.set_anchor(gtk_layer_shell::Edge::Right, false);
-                window.set_margin(gtk_layer_shell::Edge::Left, self.margin_x);
-            }
-            Alignment::Center => {
-                window.set_anchor(gtk_layer_shell::Edge::Left, false);
-                window.set_anchor(gtk_layer_shell::Edge::Right, false);
-            }
-            Alignment::End => {
-                window.set_anchor(gtk_layer_shell::Edge::Left, false);
-                window.set_anchor(gtk_layer_shell::Edge::Right, true);
-                window.set_margin(gtk_layer_shell::Edge::Right, self.margin_x);
+        problems
+    }
+
+    /// Returns the valid monitor selectors that don't match any connected monitor,
+    /// they are only warnings because the monitor can be connected later
+    pub fn unmatched_selectors(&self) -> Vec<String> {
+        let connected = connected_monitors();
+        self.monitor_selectors()
+            .iter()
+            .filter(|selector| selector.check().is_ok())
+            .filter(|selector| find_monitor(&connected, selector).is_none())
+            .map(|selector| format!("monitor \"{selector}\" doesn't match any connected monitor"))
+            .collect()
+    }
+
+    /// Selectors in `monitor` and `monitor_fallback`, without `""` and `"*"`
+    fn monitor_selectors(&self) -> Vec<MonitorSelector> {
+        let selectors = match &self.monitor {
+            MonitorConfig::Single(selector)
+                if selector.source.is_empty() || selector.source == "*" =>
+            {
+                Vec::new()
             }
+            MonitorConfig::Single(selector) => vec![selector.clone()],
+            MonitorConfig::List(selectors) => selectors.clone(),
+        };
+        selectors
+            .into_iter()
+            .chain(self.monitor_fallback.iter().cloned())
+            .collect()
+    }
+
+    /// Returns the connected monitors in `monitor`, or the first connected one in
+    /// `monitor_fallback`, `None` if none of them is connected
+    fn matched_monitors(&self) -> Option<Vec<gdk::Monitor>> {
+        let monitors = self.monitor.matching_monitors();
+        if !monitors.is_empty() {
+            return Some(monitors);
         }
-        let mut monitor = None;
-        for mon in gdk::Display::default()
-            .unwrap()
-            .monitors()
-            .iter::<gdk::Monitor>()
-        {
-            let mon = match mon {
-                Ok(monitor) => monitor,
-                Err(_) => {
-                    continue;
-                }
-            };
-            if mon.connector().unwrap().eq_ignore_ascii_case(&self.monitor) {
-                monitor = Some(mon);
-                break;
-            }
+        let connected = connected_monitors();
+        self.monitor_fallback
+            .iter()
+            .find_map(|selector| find_monitor(&connected, selector))
+            .map(|monitor| vec![monitor])
+    }
+
+    /// Finds the monitors for the window, trying `monitor_fallback` in order
+    /// and then `unmatched_monitor` if none of the monitors in `monitor` is connected,
+    /// the bool is true if `unmatched_monitor` was used
+    pub fn resolve_monitors(&self) -> (MonitorChoice, bool) {
+        if self.monitor.is_compositor_choice() {
+            return (MonitorChoice::Compositor, false);
+        }
+        if let Some(monitors) = self.matched_monitors() {
+            return (MonitorChoice::Monitors(monitors), false);
         }
+        let choice = match self.unmatched_monitor {
+            UnmatchedMonitor::Compositor => MonitorChoice::Compositor,
+            UnmatchedMonitor::First => match connected_monitors().into_iter().next() {
+                Some(monitor) => MonitorChoice::Monitors(vec![monitor]),
+                None => MonitorChoice::Compositor,
+            },
+            UnmatchedMonitor::Hide => MonitorChoice::Hidden,
+        };
+        (choice, true)
+    }
+
+    /// Returns the exclusive zone of a window of `width` x `height`,
+    /// with `auto` it's the size across the edge the window is anchored to
+    pub fn exclusive_zone_for(&self, width: i32, height: i32) -> i32 {
+        match self.exclusive_zone {
+            ExclusiveZone::Size(size) => size,
+            ExclusiveZone::Auto => match (&self.v_anchor, &self.h_anchor) {
+                (Alignment::Start | Alignment::End, _) => height,
+                (_, Alignment::Start | Alignment::End) => width,
+                _ => 0,
+            },
+        }
+    }
+
+    /// `monitor` is the monitor chosen for the window, if it's `None` the compositor chooses
+    pub fn config_layer_shell_for(&self, window: &Window, monitor: Option<&gdk::Monitor>) {
+        window.set_layer(self.layer.map_gtk());
+        let (top, bottom) = self.v_anchor.anchors();
+        let (left, right) = self.h_anchor.anchors();
+        window.set_anchor(gtk_layer_shell::Edge::Top, top);
+        window.set_anchor(gtk_layer_shell::Edge::Bottom, bottom);
+        window.set_anchor(gtk_layer_shell::Edge::Left, left);
+        window.set_anchor(gtk_layer_shell::Edge::Right, right);
+        // margins only move the window away from anchored edges
+        window.set_margin(
+            gtk_layer_shell::Edge::Top,
+            self.margin_top.unwrap_or(self.margin_y),
+        );
+        window.set_margin(
+            gtk_layer_shell::Edge::Bottom,
+            self.margin_bottom.unwrap_or(self.margin_y),
+        );
+        window.set_margin(
+            gtk_layer_shell::Edge::Left,
+            self.margin_left.unwrap_or(self.margin_x),
+        );
+        window.set_margin(
+            gtk_layer_shell::Edge::Right,
+            self.margin_right.unwrap_or(self.margin_x),
+        );
         if let Some(monitor) = monitor {
-            window.set_monitor(&monitor);
+            window.set_monitor(monitor);
         }
+        window.set_keyboard_mode(self.keyboard_mode.map_gtk());
         window.set_namespace("dynisland");
-        window.set_exclusive_zone(self.exclusive_zone);
+        window.set_exclusive_zone(self.exclusive_zone_for(window.width(), window.height()));
         window.set_resizable(false);
         window.queue_resize();
     }
 
-    pub fn init_window(&self, window: &Window) {
+    pub fn init_window(&self, window: &Window, monitor: Option<&gdk::Monitor>) {
         if self.layer_shell {
             window.init_layer_shell();
-            self.config_layer_shell_for(window.upcast_ref());
+            self.config_layer_shell_for(window.upcast_ref(), monitor);
             window.connect_destroy(|_| log::debug!("LayerShell window was destroyed"));
         } else {
             window.connect_destroy(|_| std::process::exit(0));
         }
     }
-    pub fn reconfigure_window(&self, window: &Window) {
+    /// Puts `popup` right under `island`, or above it if the island is at the bottom,
+    /// the exclusive zones of the other windows are ignored.
+    /// Returns false if there is no space for the popup, like when the island fills the height
+    pub fn init_popup(&self, popup: &Window, island: &Window) -> bool {
+        if !self.layer_shell {
+            popup.set_transient_for(Some(island));
+            popup.set_decorated(false);
+            popup.set_resizable(false);
+            return true;
+        }
+        let monitor = island
+            .surface()
+            .and_then(|surface| island.display().monitor_at_surface(&surface));
+        let (edge, margin) = match self.v_anchor {
+            Alignment::Start => (
+                gtk_layer_shell::Edge::Top,
+                self.margin_top.unwrap_or(self.margin_y),
+            ),
+            Alignment::End => (
+                gtk_layer_shell::Edge::Bottom,
+                self.margin_bottom.unwrap_or(self.margin_y),
+            ),
+            // the margins don't move a centered window, it's in the middle of the monitor
+            Alignment::Center => match &monitor {
+                Some(monitor) => (
+                    gtk_layer_shell::Edge::Top,
+                    (monitor.geometry().height() - island.height()) / 2,
+                ),
+                None => return false,
+            },
+            Alignment::Fill => return false,
+        };
+        popup.init_layer_shell();
+        popup.set_layer(self.layer.map_gtk());
+        let (left, right) = self.h_anchor.anchors();
+        popup.set_anchor(gtk_layer_shell::Edge::Left, left);
+        popup.set_anchor(gtk_layer_shell::Edge::Right, right);
+        popup.set_margin(
+            gtk_layer_shell::Edge::Left,
+            self.margin_left.unwrap_or(self.margin_x),
+        );
+        popup.set_margin(
+            gtk_layer_shell::Edge::Right,
+            self.margin_right.unwrap_or(self.margin_x),
+        );
+        popup.set_anchor(edge, true);
+        popup.set_margin(edge, margin + island.height());
+        if let Some(monitor) = &monitor {
+            popup.set_monitor(monitor);
+        }
+        // it gets the focus when clicked, losing it minimizes the activity
+        popup.set_keyboard_mode(gtk_layer_shell::KeyboardMode::OnDemand);
+        popup.set_namespace("dynisland-popup");
+        popup.set_exclusive_zone(-1);
+        popup.set_resizable(false);
+        true
+    }
+
+    pub fn reconfigure_window(&self, window: &Window, monitor: Option<&gdk::Monitor>) {
         if self.layer_shell {
-            self.config_layer_shell_for(window);
+            self.config_layer_shell_for(window, monitor);
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn position(v_anchor: Alignment, h_anchor: Alignment) -> WindowPosition {
+        WindowPosition {
+            v_anchor,
+            h_anchor,
+            exclusive_zone: ExclusiveZone::Auto,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn exclusive_zone_serde() {
+        for (json, zone) in [
+            ("-1", ExclusiveZone::Size(-1)),
+            ("0", ExclusiveZone::Size(0)),
+            ("40", ExclusiveZone::Size(40)),
+            ("\"auto\"", ExclusiveZone::Auto),
+            ("\"AUTO\"", ExclusiveZone::Auto),
+        ] {
+            assert_eq!(serde_json::from_str::<ExclusiveZone>(json).unwrap(), zone);
+        }
+        for zone in [ExclusiveZone::Size(-1), ExclusiveZone::Auto] {
+            let json = serde_json::to_string(&zone).unwrap();
+            assert_eq!(serde_json::from_str::<ExclusiveZone>(&json).unwrap(), zone);
+        }
+        assert!(serde_json::from_str::<ExclusiveZone>("\"full\"").is_err());
+        assert!(serde_json::from_str::<ExclusiveZone>("4294967296").is_err());
+        assert!(serde_json::from_str::<ExclusiveZone>("1.5").is_err());
+    }
+
+    #[test]
+    fn auto_exclusive_zone() {
+        // anchored to the top or bottom edge it's the height
+        assert_eq!(
+            position(Alignment::Start, Alignment::Fill).exclusive_zone_for(800, 40),
+            40
+        );
+        assert_eq!(
+            position(Alignment::End, Alignment::Center).exclusive_zone_for(800, 40),
+            40
+        );
+        // anchored to the left or right edge it's the width
+        assert_eq!(
+            position(Alignment::Fill, Alignment::Start).exclusive_zone_for(60, 600),
+            60
+        );
+        assert_eq!(
+            position(Alignment::Center, Alignment::End).exclusive_zone_for(60, 600),
+            60
+        );
+        // no single edge to reserve
+        assert_eq!(
+            position(Alignment::Center, Alignment::Center).exclusive_zone_for(60, 40),
+            0
+        );
+        assert_eq!(
+            position(Alignment::Fill, Alignment::Fill).exclusive_zone_for(60, 40),
+            0
+        );
+        let fixed = WindowPosition {
+            exclusive_zone: ExclusiveZone::Size(-1),
+            ..position(Alignment::Start, Alignment::Center)
+        };
+        assert_eq!(fixed.exclusive_zone_for(800, 40), -1);
+    }
+}